@@ -0,0 +1,538 @@
+use core::{
+    cell::RefCell,
+    future::Future,
+    ops::Deref,
+    pin::Pin as FuturePin,
+    sync::atomic::{ AtomicU16, Ordering },
+    task::{ Context, Poll, Waker },
+};
+
+use cortex_m::interrupt::{ self as critical, CriticalSection, Mutex };
+use embedded_hal::digital::InputPin;
+use embedded_hal_async::digital::Wait;
+
+use crate::{
+    gpio::{ pin::Input, typestate::{ mode, PXn } },
+    pac::{ self, interrupt, Interrupt },
+    rcc::{ PeripheralClock, RCC },
+    InterruptHandler,
+};
+
+/// Number of EXTI lines connected to GPIO pins.
+pub const GPIO_LINES: usize = 16;
+
+/// Number of EXTI lines, 16 GPIO lines followed by PVD, RTC alarm, USB OTG FS wakeup,
+/// Ethernet wakeup, USB OTG HS wakeup, tamper/timestamp and RTC wakeup.
+pub const LINES: u8 = 23;
+
+/// Owned handle of the external interrupt controller, obtained from
+/// [`Peripherals`](crate::Peripherals).
+pub struct EXTI(pub(crate) ());
+
+impl Deref for EXTI {
+    type Target = pac::exti::RegisterBlock;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*pac::EXTI::ptr() }
+    }
+}
+
+impl EXTI {
+    /// Connects GPIO line `line` to port `port` (0 for GPIOA to 10 for GPIOK) through
+    /// SYSCFG_EXTICR. Each line can only listen to one port at a time.
+    pub fn route_gpio(&mut self, rcc: &mut RCC, port: u8, line: u8) {
+        assert!(port < 11 && (line as usize) < GPIO_LINES);
+
+        rcc.enable_peripheral_clock(PeripheralClock::SYSCFG);
+
+        // SYSCFG is only used for the EXTI routing, which is owned by this peripheral
+        critical::free(|_| route(unsafe { &*pac::SYSCFG::ptr() }, port, line));
+    }
+
+    pub fn set_trigger(&mut self, line: u8, edge: Edge) {
+        assert!(line < LINES);
+
+        critical::free(|_| set_trigger(self, line, edge));
+    }
+
+    /// Unmasks the line and, for GPIO lines, its interrupt in the NVIC.
+    pub fn enable_interrupt(&mut self, line: u8) {
+        assert!(line < LINES);
+
+        critical::free(|_| set_interrupt(self, line, true));
+    }
+
+    pub fn disable_interrupt(&mut self, line: u8) {
+        assert!(line < LINES);
+
+        critical::free(|_| set_interrupt(self, line, false));
+    }
+
+    /// Lets the line wake the core from WFE without running an interrupt handler.
+    pub fn enable_event(&mut self, line: u8) {
+        assert!(line < LINES);
+
+        unsafe {
+            self.emr().modify(|r, w| w.bits(with_bit(r.bits(), line, true)));
+        }
+    }
+
+    pub fn disable_event(&mut self, line: u8) {
+        assert!(line < LINES);
+
+        unsafe {
+            self.emr().modify(|r, w| w.bits(with_bit(r.bits(), line, false)));
+        }
+    }
+
+    #[inline]
+    pub fn is_pending(&self, line: u8) -> bool {
+        assert!(line < LINES);
+
+        self.pr().read().bits() & (1 << line) != 0
+    }
+
+    #[inline]
+    pub fn clear_pending(&mut self, line: u8) {
+        assert!(line < LINES);
+
+        unsafe {
+            self.pr().write(|w| w.bits(1 << line));
+        }
+    }
+
+    /// Raises the line from software, as if its trigger edge had been detected.
+    #[inline]
+    pub fn trigger(&mut self, line: u8) {
+        assert!(line < LINES);
+
+        unsafe {
+            self.swier().write(|w| w.bits(1 << line));
+        }
+    }
+
+    /// Handler called from the EXTI interrupt after the pending bit of `line` is cleared.
+    pub fn set_handler(&mut self, line: u8, f: InterruptHandler) {
+        assert!((line as usize) < GPIO_LINES);
+
+        critical::free(|cs| IRQ_HANDLERS.borrow(cs).borrow_mut()[line as usize] = f);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Rising,
+    Falling,
+    Both,
+}
+
+/// GPIO inputs usable as EXTI interrupt source.
+pub trait ExtiPin {
+    /// Port index, 0 for GPIOA to 10 for GPIOK
+    fn port_index(&self) -> u8;
+
+    /// EXTI line, equal to the pin number
+    fn line(&self) -> u8;
+
+    #[inline]
+    fn make_interrupt_source(&mut self, exti: &mut EXTI, rcc: &mut RCC) {
+        exti.route_gpio(rcc, self.port_index(), self.line());
+    }
+
+    #[inline]
+    fn trigger_on_edge(&mut self, exti: &mut EXTI, edge: Edge) {
+        exti.set_trigger(self.line(), edge);
+    }
+
+    #[inline]
+    fn enable_interrupt(&mut self, exti: &mut EXTI) {
+        exti.enable_interrupt(self.line());
+    }
+
+    #[inline]
+    fn disable_interrupt(&mut self, exti: &mut EXTI) {
+        exti.disable_interrupt(self.line());
+    }
+
+    #[inline]
+    fn check_interrupt(&self) -> bool {
+        unsafe { (*pac::EXTI::ptr()).pr().read().bits() & (1 << self.line()) != 0 }
+    }
+
+    #[inline]
+    fn clear_interrupt_pending_bit(&mut self) {
+        unsafe {
+            (*pac::EXTI::ptr()).pr().write(|w| w.bits(1 << self.line()));
+        }
+    }
+}
+
+impl ExtiPin for Input {
+    #[inline]
+    fn port_index(&self) -> u8 {
+        self.pin.port_index()
+    }
+
+    #[inline]
+    fn line(&self) -> u8 {
+        self.pin.pin
+    }
+}
+
+impl<const P: char, const N: u8> ExtiPin for PXn<P, N, mode::Input> {
+    #[inline]
+    fn port_index(&self) -> u8 {
+        (P as u8) - b'A'
+    }
+
+    #[inline]
+    fn line(&self) -> u8 {
+        N
+    }
+}
+
+// The read-modify-write helpers below are called in a critical section, since an
+// `ExtiFuture` may touch the same registers from an executor without owning EXTI.
+
+fn route(syscfg: &pac::syscfg::RegisterBlock, port: u8, line: u8) {
+    let pos = (line % 4) * 4;
+    let mask = !(0b1111 << pos);
+    let val = (port as u32) << pos;
+
+    unsafe {
+        match line / 4 {
+            0 => syscfg.exticr1().modify(|r, w| w.bits((r.bits() & mask) | val)),
+            1 => syscfg.exticr2().modify(|r, w| w.bits((r.bits() & mask) | val)),
+            2 => syscfg.exticr3().modify(|r, w| w.bits((r.bits() & mask) | val)),
+            _ => syscfg.exticr4().modify(|r, w| w.bits((r.bits() & mask) | val)),
+        };
+    }
+}
+
+fn set_trigger(exti: &pac::exti::RegisterBlock, line: u8, edge: Edge) {
+    let rising = matches!(edge, Edge::Rising | Edge::Both);
+    let falling = matches!(edge, Edge::Falling | Edge::Both);
+
+    unsafe {
+        exti.rtsr().modify(|r, w| w.bits(with_bit(r.bits(), line, rising)));
+        exti.ftsr().modify(|r, w| w.bits(with_bit(r.bits(), line, falling)));
+    }
+}
+
+fn set_interrupt(exti: &pac::exti::RegisterBlock, line: u8, enabled: bool) {
+    unsafe {
+        exti.imr().modify(|r, w| w.bits(with_bit(r.bits(), line, enabled)));
+
+        if enabled {
+            if let Some(irq) = gpio_interrupt(line) {
+                pac::NVIC::unmask(irq);
+            }
+        }
+    }
+}
+
+const fn with_bit(val: u32, bit: u8, set: bool) -> u32 {
+    if set {
+        val | (1 << bit)
+    } else {
+        val & !(1 << bit)
+    }
+}
+
+fn gpio_interrupt(line: u8) -> Option<Interrupt> {
+    match line {
+        0 => Some(Interrupt::EXTI0),
+        1 => Some(Interrupt::EXTI1),
+        2 => Some(Interrupt::EXTI2),
+        3 => Some(Interrupt::EXTI3),
+        4 => Some(Interrupt::EXTI4),
+        5..=9 => Some(Interrupt::EXTI9_5),
+        10..=15 => Some(Interrupt::EXTI15_10),
+        _ => None,
+    }
+}
+
+/// Resolves once the EXTI line fires, the line is masked again afterwards.
+struct ExtiFuture {
+    line: u8,
+}
+
+impl ExtiFuture {
+    /// Routes `line` to `port` and arms it. Only the bits of `line` are touched, through
+    /// the raw register blocks and with interrupts disabled, so the owner of [`EXTI`] and
+    /// of RCC never sees them change under a read-modify-write.
+    fn new(port: u8, line: u8, edge: Edge) -> Self {
+        critical::free(|cs| {
+            let (rcc, syscfg, exti) = unsafe {
+                (&*pac::RCC::ptr(), &*pac::SYSCFG::ptr(), &*pac::EXTI::ptr())
+            };
+
+            rcc.apb2enr().modify(|_, w| w.syscfgen().set_bit());
+            route(syscfg, port, line);
+            set_trigger(exti, line, edge);
+            unsafe {
+                exti.pr().write(|w| w.bits(1 << line));
+            }
+            arm(cs, line);
+            set_interrupt(exti, line, true);
+        });
+
+        Self { line }
+    }
+}
+
+impl Future for ExtiFuture {
+    type Output = ();
+
+    fn poll(self: FuturePin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        critical::free(|cs| {
+            // `dispatch` disarms the line once it fired
+            if register_waker(cs, self.line, cx.waker()) {
+                Poll::Pending
+            } else {
+                Poll::Ready(())
+            }
+        })
+    }
+}
+
+impl Drop for ExtiFuture {
+    fn drop(&mut self) {
+        critical::free(|cs| {
+            if disarm(cs, self.line) {
+                set_interrupt(unsafe { &*pac::EXTI::ptr() }, self.line, false);
+            }
+        });
+    }
+}
+
+async fn wait_for_level<P: ExtiPin + InputPin>(pin: &mut P, high: bool) -> Result<(), P::Error> {
+    let edge = if high { Edge::Rising } else { Edge::Falling };
+    let future = ExtiFuture::new(pin.port_index(), pin.line(), edge);
+
+    // The line is armed before sampling, so a change in between is not missed
+    if pin.is_high()? == high {
+        return Ok(());
+    }
+
+    future.await;
+    Ok(())
+}
+
+async fn wait_for_edge<P: ExtiPin>(pin: &mut P, edge: Edge) {
+    ExtiFuture::new(pin.port_index(), pin.line(), edge).await
+}
+
+impl Wait for Input {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        wait_for_level(self, true).await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        wait_for_level(self, false).await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        wait_for_edge(self, Edge::Rising).await;
+        Ok(())
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        wait_for_edge(self, Edge::Falling).await;
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        wait_for_edge(self, Edge::Both).await;
+        Ok(())
+    }
+}
+
+impl<const P: char, const N: u8> Wait for PXn<P, N, mode::Input> {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        wait_for_level(self, true).await
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        wait_for_level(self, false).await
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        wait_for_edge(self, Edge::Rising).await;
+        Ok(())
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        wait_for_edge(self, Edge::Falling).await;
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        wait_for_edge(self, Edge::Both).await;
+        Ok(())
+    }
+}
+
+fn default_handler() {}
+
+static IRQ_HANDLERS: Mutex<RefCell<[InterruptHandler; GPIO_LINES]>> =
+    Mutex::new(RefCell::new([default_handler; GPIO_LINES]));
+
+/// GPIO lines awaited by an [`ExtiFuture`] that did not fire yet.
+static ARMED: AtomicU16 = AtomicU16::new(0);
+
+static WAKERS: Mutex<RefCell<[Option<Waker>; GPIO_LINES]>> =
+    Mutex::new(RefCell::new([const { None }; GPIO_LINES]));
+
+/// Marks `line` as awaited.
+fn arm(cs: &CriticalSection, line: u8) {
+    WAKERS.borrow(cs).borrow_mut()[line as usize] = None;
+    ARMED.fetch_or(1 << line, Ordering::SeqCst);
+}
+
+/// Stores `waker` for `line` if it is still armed, returns whether it was.
+fn register_waker(cs: &CriticalSection, line: u8, waker: &Waker) -> bool {
+    let armed = ARMED.load(Ordering::SeqCst) & (1 << line) != 0;
+    if armed {
+        WAKERS.borrow(cs).borrow_mut()[line as usize] = Some(waker.clone());
+    }
+    armed
+}
+
+/// Disarms `line` and drops its waker, returns whether it was armed.
+fn disarm(cs: &CriticalSection, line: u8) -> bool {
+    WAKERS.borrow(cs).borrow_mut()[line as usize] = None;
+    ARMED.fetch_and(!(1 << line), Ordering::SeqCst) & (1 << line) != 0
+}
+
+/// Disarms `line` after it fired and wakes its task, returns whether it was armed.
+fn fire(cs: &CriticalSection, line: u8) -> bool {
+    let waker = WAKERS.borrow(cs).borrow_mut()[line as usize].take();
+    let armed = disarm(cs, line);
+    if let Some(waker) = waker {
+        waker.wake();
+    }
+    armed
+}
+
+fn dispatch(first: u8, last: u8) {
+    let exti = unsafe { &*pac::EXTI::ptr() };
+    let pending = exti.pr().read().bits() & exti.imr().read().bits();
+
+    for line in first..=last {
+        if pending & (1 << line) != 0 {
+            unsafe {
+                exti.pr().write(|w| w.bits(1 << line));
+            }
+
+            let handler = critical::free(|cs| {
+                if fire(cs, line) {
+                    // One-shot, the future re-arms the line on its next wait
+                    set_interrupt(exti, line, false);
+                }
+
+                IRQ_HANDLERS.borrow(cs).borrow()[line as usize]
+            });
+
+            handler();
+        }
+    }
+}
+
+#[interrupt]
+fn EXTI0() {
+    dispatch(0, 0);
+}
+
+#[interrupt]
+fn EXTI1() {
+    dispatch(1, 1);
+}
+
+#[interrupt]
+fn EXTI2() {
+    dispatch(2, 2);
+}
+
+#[interrupt]
+fn EXTI3() {
+    dispatch(3, 3);
+}
+
+#[interrupt]
+fn EXTI4() {
+    dispatch(4, 4);
+}
+
+#[interrupt]
+fn EXTI9_5() {
+    dispatch(5, 9);
+}
+
+#[interrupt]
+fn EXTI15_10() {
+    dispatch(10, 15);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gpio_line_interrupts() {
+        assert_eq!(gpio_interrupt(0), Some(Interrupt::EXTI0));
+        assert_eq!(gpio_interrupt(4), Some(Interrupt::EXTI4));
+        assert_eq!(gpio_interrupt(7), Some(Interrupt::EXTI9_5));
+        assert_eq!(gpio_interrupt(15), Some(Interrupt::EXTI15_10));
+        assert_eq!(gpio_interrupt(16), None);
+        assert_eq!(with_bit(0b101, 1, true), 0b111);
+        assert_eq!(with_bit(0b101, 2, false), 0b001);
+    }
+
+    // Each test uses its own lines, the bookkeeping is global
+
+    #[test]
+    fn armed_line_resolves_once_fired() {
+        let cs = unsafe { CriticalSection::new() };
+        let waker = Waker::noop();
+
+        arm(&cs, 3);
+        assert!(register_waker(&cs, 3, waker));
+        assert!(stored_waker(&cs, 3).is_some());
+
+        assert!(fire(&cs, 3));
+        assert!(stored_waker(&cs, 3).is_none());
+        assert!(!register_waker(&cs, 3, waker));
+
+        // a second edge after the future resolved is not reported as armed
+        assert!(!fire(&cs, 3));
+    }
+
+    #[test]
+    fn edge_before_first_poll_is_kept() {
+        let cs = unsafe { CriticalSection::new() };
+
+        arm(&cs, 5);
+        assert!(fire(&cs, 5));
+        assert!(!register_waker(&cs, 5, Waker::noop()));
+    }
+
+    #[test]
+    fn dropped_future_disarms_its_line_only() {
+        let cs = unsafe { CriticalSection::new() };
+
+        arm(&cs, 8);
+        arm(&cs, 9);
+        assert!(register_waker(&cs, 8, Waker::noop()));
+
+        assert!(disarm(&cs, 8));
+        assert!(stored_waker(&cs, 8).is_none());
+        assert!(!disarm(&cs, 8));
+        assert!(register_waker(&cs, 9, Waker::noop()));
+        assert!(disarm(&cs, 9));
+    }
+
+    fn stored_waker(cs: &CriticalSection, line: u8) -> Option<Waker> {
+        WAKERS.borrow(cs).borrow()[line as usize].clone()
+    }
+}
@@ -0,0 +1,159 @@
+use core::ops::Deref;
+
+use crate::{ pac, rcc::{ self, wait_until }, time::Hertz };
+
+/// Owned handle of the flash interface, obtained from [`Peripherals`](crate::Peripherals).
+pub struct FLASH(pub(crate) ());
+
+impl Deref for FLASH {
+    type Target = pac::flash::RegisterBlock;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*pac::FLASH::ptr() }
+    }
+}
+
+impl FLASH {
+    #[inline]
+    pub fn latency(&self) -> u8 {
+        self.acr().read().latency().bits()
+    }
+
+    /// Programs the number of wait states and waits until the new value is in effect.
+    #[inline]
+    pub fn set_latency(&mut self, wait_states: u8) -> rcc::Result<()> {
+        unsafe {
+            self.acr().modify(|_, w| w.latency().bits(wait_states));
+        }
+        wait_until(|| self.latency() == wait_states, rcc::Error::FlashLatencyTimeout)
+    }
+
+    #[inline]
+    pub fn enable_prefetch(&mut self) {
+        self.acr().modify(|_, w| w.prften().set_bit());
+    }
+
+    #[inline]
+    pub fn disable_prefetch(&mut self) {
+        self.acr().modify(|_, w| w.prften().clear_bit());
+    }
+
+    #[inline]
+    pub fn is_prefetch_enabled(&self) -> bool {
+        self.acr().read().prften().bit_is_set()
+    }
+
+    #[inline]
+    pub fn enable_instruction_cache(&mut self) {
+        self.acr().modify(|_, w| w.icen().set_bit());
+    }
+
+    #[inline]
+    pub fn disable_instruction_cache(&mut self) {
+        self.acr().modify(|_, w| w.icen().clear_bit());
+    }
+
+    #[inline]
+    pub fn is_instruction_cache_enabled(&self) -> bool {
+        self.acr().read().icen().bit_is_set()
+    }
+
+    /// Invalidates the instruction cache. The cache is disabled while being reset and
+    /// re-enabled afterwards if it was enabled before.
+    pub fn reset_instruction_cache(&mut self) {
+        let enabled = self.is_instruction_cache_enabled();
+
+        self.disable_instruction_cache();
+        self.acr().modify(|_, w| w.icrst().set_bit());
+        self.acr().modify(|_, w| w.icrst().clear_bit());
+
+        if enabled {
+            self.enable_instruction_cache();
+        }
+    }
+
+    #[inline]
+    pub fn enable_data_cache(&mut self) {
+        self.acr().modify(|_, w| w.dcen().set_bit());
+    }
+
+    #[inline]
+    pub fn disable_data_cache(&mut self) {
+        self.acr().modify(|_, w| w.dcen().clear_bit());
+    }
+
+    #[inline]
+    pub fn is_data_cache_enabled(&self) -> bool {
+        self.acr().read().dcen().bit_is_set()
+    }
+
+    /// Invalidates the data cache. The cache is disabled while being reset and
+    /// re-enabled afterwards if it was enabled before.
+    pub fn reset_data_cache(&mut self) {
+        let enabled = self.is_data_cache_enabled();
+
+        self.disable_data_cache();
+        self.acr().modify(|_, w| w.dcrst().set_bit());
+        self.acr().modify(|_, w| w.dcrst().clear_bit());
+
+        if enabled {
+            self.enable_data_cache();
+        }
+    }
+}
+
+/// Supply voltage range of the device, used to select the flash wait states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoltageRange {
+    /// 1.8 V to 2.1 V, prefetch must stay disabled
+    V1_8To2_1,
+    /// 2.1 V to 2.4 V
+    V2_1To2_4,
+    /// 2.4 V to 2.7 V
+    V2_4To2_7,
+    /// 2.7 V to 3.6 V
+    V2_7To3_6,
+}
+
+impl VoltageRange {
+    /// Number of flash wait states required to run at `hclk`, or `None` if the
+    /// frequency is above the maximum allowed for this voltage range.
+    ///
+    /// See RM0090 Table 11 "Number of wait states according to CPU clock (HCLK) frequency".
+    pub const fn wait_states(&self, hclk: Hertz) -> Option<u8> {
+        let hclk = hclk.raw();
+        let (step, max) = match self {
+            VoltageRange::V1_8To2_1 => (20_000_000, 168_000_000),
+            VoltageRange::V2_1To2_4 => (22_000_000, 180_000_000),
+            VoltageRange::V2_4To2_7 => (24_000_000, 180_000_000),
+            VoltageRange::V2_7To3_6 => (30_000_000, 180_000_000),
+        };
+
+        if hclk > max {
+            return None;
+        }
+
+        match hclk.div_ceil(step) {
+            0 => Some(0),
+            n => Some((n - 1) as u8),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_states() {
+        assert_eq!(VoltageRange::V2_7To3_6.wait_states(Hertz::MHz(16)), Some(0));
+        assert_eq!(VoltageRange::V2_7To3_6.wait_states(Hertz::MHz(30)), Some(0));
+        assert_eq!(VoltageRange::V2_7To3_6.wait_states(Hertz::Hz(30_000_001)), Some(1));
+        assert_eq!(VoltageRange::V2_7To3_6.wait_states(Hertz::MHz(168)), Some(5));
+        assert_eq!(VoltageRange::V2_7To3_6.wait_states(Hertz::MHz(180)), Some(5));
+        assert_eq!(VoltageRange::V2_4To2_7.wait_states(Hertz::MHz(180)), Some(7));
+        assert_eq!(VoltageRange::V2_1To2_4.wait_states(Hertz::MHz(180)), Some(8));
+        assert_eq!(VoltageRange::V1_8To2_1.wait_states(Hertz::MHz(168)), Some(8));
+        assert_eq!(VoltageRange::V1_8To2_1.wait_states(Hertz::MHz(180)), None);
+    }
+}
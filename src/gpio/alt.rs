@@ -0,0 +1,99 @@
+//! Legal pin and alternate function pairs of each peripheral signal.
+//!
+//! Transcribed from the STM32F427xx/STM32F429xx datasheet (DS9405), Table 12
+//! "STM32F427xx and STM32F429xx alternate function mapping". A signal trait is only
+//! implemented for a pin configured with the AF routing that signal to it, so wiring a
+//! driver to the wrong pin or AF number does not compile. I2C signals additionally require
+//! the open-drain alternate mode.
+
+use crate::{
+    i2c::{ self, I2C1, I2C2, I2C3 },
+    spi::{ self, SPI1, SPI2, SPI3, SPI4, SPI5, SPI6 },
+};
+
+use super::typestate::{
+    mode::{ Alternate, AlternateOpenDrain },
+    PA,
+    PB,
+    PC,
+    PD,
+    PE,
+    PF,
+    PG,
+    PH,
+    PI,
+};
+
+/// Placeholder for an unused optional signal, e.g. MOSI of a receive-only SPI.
+pub struct NoPin;
+
+impl<SPI> spi::MisoPin<SPI> for NoPin {}
+
+impl<SPI> spi::MosiPin<SPI> for NoPin {}
+
+macro_rules! alt_pins {
+    ($mode:ident: $($signal:path: $($port:ident<$n:literal, $af:literal>),+;)+) => {
+        $($(
+            impl $signal for $port<$n, $mode<$af>> {}
+        )+)+
+    };
+}
+
+alt_pins! {
+    Alternate:
+
+    spi::SckPin<SPI1>: PA<5, 5>, PB<3, 5>;
+    spi::MisoPin<SPI1>: PA<6, 5>, PB<4, 5>;
+    spi::MosiPin<SPI1>: PA<7, 5>, PB<5, 5>;
+
+    spi::SckPin<SPI2>: PB<10, 5>, PB<13, 5>, PD<3, 5>, PI<1, 5>;
+    spi::MisoPin<SPI2>: PB<14, 5>, PC<2, 5>, PI<2, 5>;
+    spi::MosiPin<SPI2>: PB<15, 5>, PC<3, 5>, PI<3, 5>;
+
+    spi::SckPin<SPI3>: PB<3, 6>, PC<10, 6>;
+    spi::MisoPin<SPI3>: PB<4, 6>, PC<11, 6>;
+    spi::MosiPin<SPI3>: PB<5, 6>, PC<12, 6>, PD<6, 5>;
+
+    spi::SckPin<SPI4>: PE<2, 5>, PE<12, 5>;
+    spi::MisoPin<SPI4>: PE<5, 5>, PE<13, 5>;
+    spi::MosiPin<SPI4>: PE<6, 5>, PE<14, 5>;
+
+    spi::SckPin<SPI5>: PF<7, 5>, PH<6, 5>;
+    spi::MisoPin<SPI5>: PF<8, 5>, PH<7, 5>;
+    spi::MosiPin<SPI5>: PF<9, 5>, PF<11, 5>;
+
+    spi::SckPin<SPI6>: PG<13, 5>;
+    spi::MisoPin<SPI6>: PG<12, 5>;
+    spi::MosiPin<SPI6>: PG<14, 5>;
+}
+
+alt_pins! {
+    AlternateOpenDrain:
+
+    i2c::SclPin<I2C1>: PB<6, 4>, PB<8, 4>;
+    i2c::SdaPin<I2C1>: PB<7, 4>, PB<9, 4>;
+
+    i2c::SclPin<I2C2>: PB<10, 4>, PF<1, 4>, PH<4, 4>;
+    i2c::SdaPin<I2C2>: PB<11, 4>, PF<0, 4>, PH<5, 4>;
+
+    i2c::SclPin<I2C3>: PA<8, 4>, PH<7, 4>;
+    i2c::SdaPin<I2C3>: PC<9, 4>, PH<8, 4>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spi<SPI, SCK: spi::SckPin<SPI>, MISO: spi::MisoPin<SPI>, MOSI: spi::MosiPin<SPI>>() {}
+
+    fn i2c<I2C, SCL: i2c::SclPin<I2C>, SDA: i2c::SdaPin<I2C>>() {}
+
+    #[test]
+    fn board_pins() {
+        // L3GD20 gyroscope and ILI9341 display of the STM32F429I-DISCO
+        spi::<SPI5, PF<7, Alternate<5>>, PF<8, Alternate<5>>, PF<9, Alternate<5>>>();
+        spi::<SPI5, PF<7, Alternate<5>>, NoPin, PF<9, Alternate<5>>>();
+        // STMPE811 touch controller
+        i2c::<I2C3, PA<8, AlternateOpenDrain<4>>, PC<9, AlternateOpenDrain<4>>>();
+    }
+}
@@ -1,10 +1,17 @@
+pub mod alt;
 pub mod pin;
+pub mod typestate;
 
-use core::{ops::{ BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Deref, Not }, ptr::addr_of};
+use core::{
+    fmt,
+    ops::{ BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, BitXorAssign, Deref, DerefMut, Not },
+};
 
-use crate::{ pac, rcc::RCC, Peripheral, PeripheralRef };
+use cortex_m::interrupt;
 
-use self::pin::{ OutputType, Pin, PinConfig, PinMode, Pull, Speed };
+use crate::{ pac, rcc::{ PeripheralClock, RCC }, Peripheral };
+
+use self::{ pin::{ OutputType, Pin, PinConfig, PinMode, Pull, Speed }, typestate::Parts };
 
 pub struct Port(pac::gpioa::RegisterBlock);
 
@@ -16,149 +23,65 @@ impl Deref for Port {
     }
 }
 
-pub struct GPIOA;
-
-impl PeripheralRef for GPIOA {
-    type Output = Port;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOA::PTR as *mut _) }
-    }
-}
-
-pub struct GPIOB;
-
-impl PeripheralRef for GPIOB {
-    type Output = Port;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOB::PTR as *mut _) }
-    }
-}
-
-pub struct GPIOC;
-
-impl PeripheralRef for GPIOC {
-    type Output = Port;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOC::PTR as *mut _) }
-    }
-}
-
-pub struct GPIOD;
-
-impl PeripheralRef for GPIOD {
-    type Output = Port;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOD::PTR as *mut _) }
-    }
-}
-
-pub struct GPIOE;
-
-impl PeripheralRef for GPIOE {
-    type Output = Port;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOE::PTR as *mut _) }
-    }
-}
-
-pub struct GPIOF;
-
-impl PeripheralRef for GPIOF {
-    type Output = Port;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOF::PTR as *mut _) }
-    }
-}
-
-pub struct GPIOG;
-
-impl PeripheralRef for GPIOG {
-    type Output = Port;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOG::PTR as *mut _) }
-    }
-}
-
-pub struct GPIOH;
-
-impl PeripheralRef for GPIOH {
-    type Output = Port;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOH::PTR as *mut _) }
-    }
-}
-
-pub struct GPIOI;
-
-impl PeripheralRef for GPIOI {
-    type Output = Port;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOI::PTR as *mut _) }
-    }
-}
-
-pub struct GPIOJ;
+macro_rules! gpio {
+    ($($GPIOX:ident: $port:literal;)+) => {
+        $(
+            #[doc = concat!(
+                "Owned handle of ", stringify!($GPIOX),
+                ", obtained from [`Peripherals`](crate::Peripherals)."
+            )]
+            ///
+            /// Derefs to [`Port`] for mask-wide access until it is consumed by `split`.
+            pub struct $GPIOX(pub(crate) ());
+
+            impl $GPIOX {
+                /// Splits the port into its 16 typestate pins and enables the port clock.
+                #[inline]
+                pub fn split(mut self, rcc: &mut RCC) -> Parts<$port> {
+                    self.enable_clock(rcc);
+                    Parts::new()
+                }
+            }
 
-impl PeripheralRef for GPIOJ {
-    type Output = Port;
+            impl Deref for $GPIOX {
+                type Target = Port;
 
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOJ::PTR as *mut _) }
-    }
-}
-
-pub struct GPIOK;
+                fn deref(&self) -> &Self::Target {
+                    unsafe { &*(pac::$GPIOX::PTR as *const _) }
+                }
+            }
 
-impl PeripheralRef for GPIOK {
-    type Output = Port;
+            impl DerefMut for $GPIOX {
+                fn deref_mut(&mut self) -> &mut Self::Target {
+                    unsafe { &mut *(pac::$GPIOX::PTR as *mut _) }
+                }
+            }
 
-    fn take() -> &'static mut Self::Output {
-        unsafe { &mut *(pac::GPIOK::PTR as *mut _) }
-    }
+            impl Peripheral for $GPIOX {
+                const CLOCK: PeripheralClock = PeripheralClock::$GPIOX;
+            }
+        )+
+    };
 }
 
-impl Peripheral for Port {
-    #[inline]
-    fn enable_clock(&mut self) {
-        unsafe {
-            let rcc = RCC::take();
-            let p = self.get_port_num();
-            rcc.ahb1enr().modify(#[inline] |r, w| w.bits(r.bits() | (1u32 << p)));
-        }
-    }
-
-    #[inline]
-    fn disable_clock(&mut self) {
-        unsafe {
-            let rcc = RCC::take();
-            let p = self.get_port_num();
-            rcc.ahb1enr().modify(#[inline] |r, w| w.bits(r.bits() & !(1u32 << p)));
-        }
-    }
-
-    #[inline]
-    fn reset(&mut self) {
-        unsafe {
-            let rcc = RCC::take();
-            let p = self.get_port_num();
-            rcc.ahb1rstr().modify(#[inline] |r, w| w.bits(r.bits() | (1u32 << p)));
-            rcc.ahb1rstr().modify(#[inline] |r, w| w.bits(r.bits() & !(1u32 << p)));
-        }
-    }
+gpio! {
+    GPIOA: 'A';
+    GPIOB: 'B';
+    GPIOC: 'C';
+    GPIOD: 'D';
+    GPIOE: 'E';
+    GPIOF: 'F';
+    GPIOG: 'G';
+    GPIOH: 'H';
+    GPIOI: 'I';
+    GPIOJ: 'J';
+    GPIOK: 'K';
 }
 
 impl Port {
+    /// Untracked pin of this port, crate code only: pins are handed out by `GPIOx::split`.
     #[inline]
-    pub fn pin(&self, pin: u8) -> Pin {
+    pub(crate) fn pin(&self, pin: u8) -> Pin {
         assert!(pin < 16);
 
         unsafe {
@@ -205,6 +128,61 @@ impl Port {
         }
     }
 
+    /// Reads back the configuration of pin `pin`.
+    #[inline]
+    pub fn pin_config(&self, pin: u8) -> PinConfig {
+        assert!(pin < 16);
+
+        Self::decode_pin_config(
+            pin,
+            self.0.moder().read().bits(),
+            self.0.otyper().read().bits(),
+            self.0.ospeedr().read().bits(),
+            self.0.pupdr().read().bits(),
+            self.read_afr()
+        )
+    }
+
+    /// Reads back the configuration of all 16 pins, e.g. to print the board setup.
+    pub fn dump(&self) -> [PinConfig; 16] {
+        let moder = self.0.moder().read().bits();
+        let otyper = self.0.otyper().read().bits();
+        let ospeedr = self.0.ospeedr().read().bits();
+        let pupdr = self.0.pupdr().read().bits();
+        let afr = self.read_afr();
+
+        core::array::from_fn(|pin| {
+            Self::decode_pin_config(pin as u8, moder, otyper, ospeedr, pupdr, afr)
+        })
+    }
+
+    /// AFRH:AFRL as one 64-bit field, 4 bits per pin.
+    #[inline]
+    fn read_afr(&self) -> u64 {
+        let afrl = self.0.afrl().read().bits() as u64;
+        let afrh = self.0.afrh().read().bits() as u64;
+
+        (afrh << 32) | afrl
+    }
+
+    #[inline]
+    fn decode_pin_config(
+        pin: u8,
+        moder: u32,
+        otyper: u32,
+        ospeedr: u32,
+        pupdr: u32,
+        afr: u64
+    ) -> PinConfig {
+        PinConfig::from_bits(
+            (moder >> (pin * 2)) & 0b11,
+            (otyper >> pin) & 0b1,
+            (ospeedr >> (pin * 2)) & 0b11,
+            (pupdr >> (pin * 2)) & 0b11,
+            ((afr >> (pin * 4)) & 0b1111) as u32
+        )
+    }
+
     #[inline]
     pub fn init_pins(&mut self, pins: impl Into<PinMask>, conf: PinConfig) {
         match conf {
@@ -357,6 +335,41 @@ impl Port {
         }
     }
 
+    /// Freezes the configuration of `pins` until the next reset.
+    ///
+    /// The lock key can only be set once per reset, so all pins of the port that must be
+    /// locked have to be passed in a single call. `Pin` setters that would change a locked
+    /// pin fail with [`Error::Locked`], bulk `set_pins_*` writes are ignored by the
+    /// hardware.
+    pub fn lock_pins(&mut self, pins: impl Into<PinMask>) -> Result<()> {
+        const LCKK: u32 = 1 << 16;
+
+        let mask = u32::from(pins.into());
+
+        // The write/write/write/read sequence must not be interrupted
+        let lckr = interrupt::free(|_| unsafe {
+            self.0.lckr().write(|w| w.bits(LCKK | mask));
+            self.0.lckr().write(|w| w.bits(mask));
+            self.0.lckr().write(|w| w.bits(LCKK | mask));
+            self.0.lckr().read();
+            self.0.lckr().read().bits()
+        });
+
+        if lckr & LCKK == 0 || lckr & mask != mask {
+            return Err(Error::LockFailed);
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn is_locked(&self, pin: u8) -> bool {
+        assert!(pin < 16);
+
+        let lckr = self.0.lckr().read();
+        lckr.lckk().bit_is_set() && (lckr.bits() & (1 << pin)) != 0
+    }
+
     #[inline]
     fn write_2bit_value_by_mask(mut bits: u32, mut value: u32, mut mask: u32) -> u32 {
         let mut mask2 = 0b11u32;
@@ -372,10 +385,6 @@ impl Port {
 
         bits
     }
-
-    unsafe fn get_port_num(&self) -> isize {
-        addr_of!(self.0).byte_offset_from(pac::GPIOA::PTR) / 0x400
-    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
@@ -510,3 +519,57 @@ impl PinMask {
         *self &= !mask;
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The pin configuration is frozen by [`Port::lock_pins`]
+    Locked,
+    /// The lock key sequence was rejected, e.g. because the port is already locked
+    LockFailed,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Locked => f.write_str("Pin Locked"),
+            Error::LockFailed => f.write_str("Lock Sequence Failed"),
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+/// Result of a pin conversion, hands the pin back together with the error on failure.
+pub type Converted<FROM, TO> = core::result::Result<TO, (FROM, Error)>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_pin_config_reads_afrh_for_high_pins() {
+        // PA9 on AF7 and PA10 on AF12, both push-pull very high speed with pull-up,
+        // next to PA2 on AF3 in AFRL.
+        let moder = (0b10 << 18) | (0b10 << 20) | (0b10 << 4);
+        let ospeedr = (0b11 << 18) | (0b11 << 20);
+        let pupdr = (0b01 << 18) | (0b01 << 20);
+        let afr = (7 << 36) | (12 << 40) | (3 << 8);
+
+        assert_eq!(
+            Port::decode_pin_config(9, moder, 0, ospeedr, pupdr, afr),
+            PinConfig::Alternate(OutputType::PushPull, Speed::VeryHigh, Pull::Up, 7)
+        );
+        assert_eq!(
+            Port::decode_pin_config(10, moder, 0, ospeedr, pupdr, afr),
+            PinConfig::Alternate(OutputType::PushPull, Speed::VeryHigh, Pull::Up, 12)
+        );
+        assert_eq!(
+            Port::decode_pin_config(2, moder, 0, 0, 0, afr),
+            PinConfig::Alternate(OutputType::PushPull, Speed::Low, Pull::None, 3)
+        );
+        assert_eq!(
+            Port::decode_pin_config(15, moder | (0b01 << 30), 1 << 15, 0, 0b10 << 30, afr),
+            PinConfig::OpenDrain(Speed::Low, Pull::Down)
+        );
+    }
+}
@@ -1,6 +1,8 @@
-use core::convert::Infallible;
+use core::{ convert::Infallible, mem::ManuallyDrop };
 
-use super::Port;
+use crate::pac;
+
+use super::{ Converted, Error, Port, Result as GpioResult };
 
 use embedded_hal::digital::{ ErrorType, InputPin, OutputPin, StatefulOutputPin };
 
@@ -53,23 +55,29 @@ impl StatefulOutputPin for Pin {
 
 impl Pin {
     #[inline]
-    pub fn set_mode(&mut self, mode: PinMode) {
+    pub fn set_mode(&mut self, mode: PinMode) -> GpioResult<()> {
         const MASK: u32 = 0b11;
 
+        let pos = self.pin * 2;
+        self.check_writable((self.port.moder().read().bits() >> pos) & MASK, mode as u32)?;
+
         unsafe {
-            let pos = self.pin * 2;
             self.port.moder().modify(|r, w| {
                 w.bits((r.bits() & !(MASK << pos)) | ((mode as u32) << pos))
             });
         }
+
+        Ok(())
     }
 
     #[inline]
-    pub fn set_output_type(&mut self, output_type: OutputType) {
+    pub fn set_output_type(&mut self, output_type: OutputType) -> GpioResult<()> {
         const MASK: u32 = 0b1;
 
+        let pos = self.pin;
+        self.check_writable((self.port.otyper().read().bits() >> pos) & MASK, output_type as u32)?;
+
         unsafe {
-            let pos = self.pin;
             self.port.otyper().modify(|r, w| {
                 match output_type {
                     OutputType::PushPull => w.bits(r.bits() & !(MASK << pos)),
@@ -77,36 +85,53 @@ impl Pin {
                 }
             });
         }
+
+        Ok(())
     }
 
     #[inline]
-    pub fn set_speed(&mut self, speed: Speed) {
+    pub fn set_speed(&mut self, speed: Speed) -> GpioResult<()> {
         const MASK: u32 = 0b11;
 
+        let pos = self.pin * 2;
+        self.check_writable((self.port.ospeedr().read().bits() >> pos) & MASK, speed as u32)?;
+
         unsafe {
-            let pos = self.pin * 2;
             self.port.ospeedr().modify(|r, w| {
                 w.bits((r.bits() & !(MASK << pos)) | ((speed as u32) << pos))
             });
         }
+
+        Ok(())
     }
 
     #[inline]
-    pub fn set_pull(&mut self, pull: Pull) {
+    pub fn set_pull(&mut self, pull: Pull) -> GpioResult<()> {
         const MASK: u32 = 0b11;
 
+        let pos = self.pin * 2;
+        self.check_writable((self.port.pupdr().read().bits() >> pos) & MASK, pull as u32)?;
+
         unsafe {
-            let pos = self.pin * 2;
             self.port.pupdr().modify(|r, w| {
                 w.bits((r.bits() & !(MASK << pos)) | ((pull as u32) << pos))
             });
         }
+
+        Ok(())
     }
 
     #[inline]
-    pub fn set_alternate_function(&mut self, func: u8) {
+    pub fn set_alternate_function(&mut self, func: u8) -> GpioResult<()> {
         const MASK: u32 = 0b1111;
 
+        let afr = if self.pin < 8 {
+            self.port.afrl().read().bits()
+        } else {
+            self.port.afrh().read().bits()
+        };
+        self.check_writable((afr >> ((self.pin % 8) * 4)) & MASK, func as u32)?;
+
         unsafe {
             if self.pin < 8 {
                 let pos = self.pin * 4;
@@ -120,17 +145,71 @@ impl Pin {
                 });
             }
         }
+
+        Ok(())
     }
-}
 
-// pub type InterruptHandler = fn();
+    /// Writes the output data register. In input mode the level is only driven once the
+    /// pin is switched to output, which avoids a glitch on the line.
+    #[inline]
+    pub fn set_level(&mut self, level: Level) {
+        let _ = match level {
+            Level::Low => self.set_low(),
+            Level::High => self.set_high(),
+        };
+    }
 
-// fn default_handler() {}
+    /// Index of the port in the GPIO address space, 0 for GPIOA to 10 for GPIOK.
+    #[inline]
+    pub(crate) fn port_index(&self) -> u8 {
+        ((self.port as *const _ as usize - pac::GPIOA::PTR as usize) / 0x400) as u8
+    }
+
+    /// Whether the configuration of this pin is frozen by [`Port::lock_pins`].
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.port.is_locked(self.pin)
+    }
+
+    /// A locked pin only rejects writes that would change its configuration, so a driver
+    /// can still take over a pin that was configured and locked beforehand.
+    #[inline]
+    fn check_writable(&self, current: u32, value: u32) -> GpioResult<()> {
+        if current != value && self.is_locked() {
+            Err(Error::Locked)
+        } else {
+            Ok(())
+        }
+    }
 
-// static mut IRQ_HANDLERS: [InterruptHandler; 16] = [default_handler; 16];
+    /// Runs the setters in `f`, handing the pin back with the error if one of them fails.
+    #[inline]
+    pub(crate) fn configure(
+        mut self,
+        f: impl FnOnce(&mut Pin) -> GpioResult<()>
+    ) -> Converted<Pin, Pin> {
+        match f(&mut self) {
+            Ok(()) => Ok(self),
+            Err(err) => Err((self, err)),
+        }
+    }
+
+    /// Returns the pin to its reset state, analog mode, unless it is locked.
+    fn reset(&mut self) {
+        if self.is_locked() {
+            return;
+        }
+
+        let _ = self.set_mode(PinMode::Analog);
+        let _ = self.set_output_type(OutputType::PushPull);
+        let _ = self.set_speed(Speed::Low);
+        let _ = self.set_pull(Pull::None);
+        let _ = self.set_alternate_function(0);
+    }
+}
 
 pub struct Input {
-    pin: Pin,
+    pub(crate) pin: Pin,
 }
 
 impl ErrorType for Input {
@@ -149,28 +228,31 @@ impl InputPin for Input {
 
 impl Input {
     #[inline]
-    pub fn new(pin: Pin, pull: Pull) -> Self {
-        let mut input = Self { pin };
+    pub fn new(pin: Pin, pull: Pull) -> Converted<Pin, Self> {
+        pin.configure(|pin| {
+            pin.set_pull(pull)?;
+            pin.set_mode(PinMode::Input)
+        })
+        .map(|pin| Self { pin })
+    }
 
-        input.pin.set_mode(PinMode::Input);
-        input.pin.set_pull(pull);
-        input
+    /// Switches to push-pull output, driving `initial_level` from the first cycle.
+    #[inline]
+    pub fn into_output(self, speed: Speed, initial_level: Level) -> Converted<Self, Output> {
+        Output::with_level(self.into_inner(), speed, initial_level)
+            .map_err(|(pin, err)| (Self { pin }, err))
     }
 }
 
 impl Drop for Input {
     #[inline]
     fn drop(&mut self) {
-        self.pin.set_mode(PinMode::Analog);
-        self.pin.set_output_type(OutputType::PushPull);
-        self.pin.set_speed(Speed::Low);
-        self.pin.set_pull(Pull::None);
-        self.pin.set_alternate_function(0);
+        self.pin.reset();
     }
 }
 
 pub struct Output {
-    pin: Pin,
+    pub(crate) pin: Pin,
 }
 
 impl ErrorType for Output {
@@ -199,30 +281,40 @@ impl StatefulOutputPin for Output {
 
 impl Output {
     #[inline]
-    pub fn new(pin: Pin, speed: Speed) -> Self {
-        let mut output = Self { pin };
+    pub fn new(pin: Pin, speed: Speed) -> Converted<Pin, Self> {
+        pin.configure(|pin| {
+            pin.set_output_type(OutputType::PushPull)?;
+            pin.set_speed(speed)?;
+            pin.set_pull(Pull::None)?;
+            pin.set_mode(PinMode::Output)
+        })
+        .map(|pin| Self { pin })
+    }
+
+    /// Like [`new`](Self::new), with the output data register set before the pin
+    /// starts driving the line.
+    #[inline]
+    pub fn with_level(mut pin: Pin, speed: Speed, initial_level: Level) -> Converted<Pin, Self> {
+        pin.set_level(initial_level);
+        Self::new(pin, speed)
+    }
 
-        output.pin.set_mode(PinMode::Output);
-        output.pin.set_output_type(OutputType::PushPull);
-        output.pin.set_speed(speed);
-        output.pin.set_pull(Pull::None);
-        output
+    /// Switches to input in place, without going through analog mode.
+    #[inline]
+    pub fn into_input(self, pull: Pull) -> Converted<Self, Input> {
+        Input::new(self.into_inner(), pull).map_err(|(pin, err)| (Self { pin }, err))
     }
 }
 
 impl Drop for Output {
     #[inline]
     fn drop(&mut self) {
-        self.pin.set_mode(PinMode::Analog);
-        self.pin.set_output_type(OutputType::PushPull);
-        self.pin.set_speed(Speed::Low);
-        self.pin.set_pull(Pull::None);
-        self.pin.set_alternate_function(0);
+        self.pin.reset();
     }
 }
 
 pub struct OpenDrain {
-    pin: Pin,
+    pub(crate) pin: Pin,
 }
 
 impl ErrorType for OpenDrain {
@@ -251,80 +343,205 @@ impl StatefulOutputPin for OpenDrain {
 
 impl OpenDrain {
     #[inline]
-    pub fn new(pin: Pin, speed: Speed, pull: Pull) -> Self {
-        let mut od = Self { pin };
+    pub fn new(pin: Pin, speed: Speed, pull: Pull) -> Converted<Pin, Self> {
+        pin.configure(|pin| {
+            pin.set_output_type(OutputType::OpenDrain)?;
+            pin.set_speed(speed)?;
+            pin.set_pull(pull)?;
+            pin.set_mode(PinMode::Output)
+        })
+        .map(|pin| Self { pin })
+    }
+
+    /// Like [`new`](Self::new), with the output data register set before the pin
+    /// starts driving the line.
+    #[inline]
+    pub fn with_level(
+        mut pin: Pin,
+        speed: Speed,
+        pull: Pull,
+        initial_level: Level
+    ) -> Converted<Pin, Self> {
+        pin.set_level(initial_level);
+        Self::new(pin, speed, pull)
+    }
 
-        od.pin.set_mode(PinMode::Output);
-        od.pin.set_output_type(OutputType::OpenDrain);
-        od.pin.set_speed(speed);
-        od.pin.set_pull(pull);
-        od
+    /// Switches to input in place, without going through analog mode.
+    #[inline]
+    pub fn into_input(self, pull: Pull) -> Converted<Self, Input> {
+        Input::new(self.into_inner(), pull).map_err(|(pin, err)| (Self { pin }, err))
     }
 }
 
 impl Drop for OpenDrain {
     #[inline]
     fn drop(&mut self) {
-        self.pin.set_mode(PinMode::Analog);
-        self.pin.set_output_type(OutputType::PushPull);
-        self.pin.set_speed(Speed::Low);
-        self.pin.set_pull(Pull::None);
-        self.pin.set_alternate_function(0);
+        self.pin.reset();
     }
 }
 
 pub struct Alternate {
-    pin: Pin,
+    pub(crate) pin: Pin,
 }
 
 impl Alternate {
     #[inline]
-    pub fn new(pin: Pin, otype: OutputType, speed: Speed, pull: Pull, func: u8) -> Self {
-        let mut af = Self { pin };
-
-        af.pin.set_mode(PinMode::Alternate);
-        af.pin.set_output_type(otype);
-        af.pin.set_speed(speed);
-        af.pin.set_pull(pull);
-        af.pin.set_alternate_function(func);
-        af
+    pub fn new(
+        pin: Pin,
+        otype: OutputType,
+        speed: Speed,
+        pull: Pull,
+        func: u8
+    ) -> Converted<Pin, Self> {
+        pin.configure(|pin| {
+            pin.set_output_type(otype)?;
+            pin.set_speed(speed)?;
+            pin.set_pull(pull)?;
+            pin.set_alternate_function(func)?;
+            pin.set_mode(PinMode::Alternate)
+        })
+        .map(|pin| Self { pin })
     }
 }
 
 impl Drop for Alternate {
     #[inline]
     fn drop(&mut self) {
-        self.pin.set_mode(PinMode::Analog);
-        self.pin.set_output_type(OutputType::PushPull);
-        self.pin.set_speed(Speed::Low);
-        self.pin.set_pull(Pull::None);
-        self.pin.set_alternate_function(0);
+        self.pin.reset();
     }
 }
 
 pub struct Analog {
-    pin: Pin,
+    pub(crate) pin: Pin,
 }
 
 impl Analog {
+    #[inline]
+    pub fn new(pin: Pin) -> Converted<Pin, Self> {
+        pin.configure(|pin| {
+            pin.set_mode(PinMode::Analog)?;
+            pin.set_pull(Pull::None)
+        })
+        .map(|pin| Self { pin })
+    }
+}
+
+impl Drop for Analog {
+    #[inline]
+    fn drop(&mut self) {
+        self.pin.reset();
+    }
+}
+
+macro_rules! into_inner {
+    ($($wrapper:ident),+) => {
+        $(
+            impl $wrapper {
+                /// Releases the pin without resetting its configuration.
+                #[inline]
+                pub fn into_inner(self) -> Pin {
+                    let this = ManuallyDrop::new(self);
+                    Pin { port: this.pin.port, pin: this.pin.pin }
+                }
+            }
+        )+
+    };
+}
+
+into_inner!(Input, Output, OpenDrain, Alternate, Analog);
+
+/// Pin switching between input and output at runtime, e.g. for one-wire buses.
+///
+/// Changing direction reconfigures the pin in place, it is only returned to analog mode
+/// when the `Flex` itself is dropped.
+pub struct Flex {
+    pub(crate) pin: Pin,
+}
+
+impl ErrorType for Flex {
+    type Error = <Pin as ErrorType>::Error;
+}
+
+impl InputPin for Flex {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.pin.is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.pin.is_low()
+    }
+}
+
+impl OutputPin for Flex {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.pin.set_high()
+    }
+}
+
+impl StatefulOutputPin for Flex {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        self.pin.is_set_high()
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        self.pin.is_set_low()
+    }
+}
+
+impl Flex {
+    /// Takes over `pin` without changing its configuration.
     #[inline]
     pub fn new(pin: Pin) -> Self {
-        let mut analog = Self { pin };
+        Self { pin }
+    }
 
-        analog.pin.set_mode(PinMode::Analog);
-        analog.pin.set_pull(Pull::None);
-        analog
+    #[inline]
+    pub fn set_as_input(&mut self, pull: Pull) -> GpioResult<()> {
+        self.pin.set_pull(pull)?;
+        self.pin.set_mode(PinMode::Input)
+    }
+
+    /// Push-pull output, driving the level last written to the output data register.
+    #[inline]
+    pub fn set_as_output(&mut self, speed: Speed) -> GpioResult<()> {
+        self.pin.set_output_type(OutputType::PushPull)?;
+        self.pin.set_speed(speed)?;
+        self.pin.set_pull(Pull::None)?;
+        self.pin.set_mode(PinMode::Output)
+    }
+
+    /// Like [`set_as_output`](Self::set_as_output), with the output data register set
+    /// before the pin starts driving the line.
+    #[inline]
+    pub fn set_as_output_with_level(&mut self, speed: Speed, level: Level) -> GpioResult<()> {
+        self.pin.set_level(level);
+        self.set_as_output(speed)
+    }
+
+    /// Open-drain output, reads return the level of the line.
+    #[inline]
+    pub fn set_as_open_drain(&mut self, speed: Speed, pull: Pull) -> GpioResult<()> {
+        self.pin.set_output_type(OutputType::OpenDrain)?;
+        self.pin.set_speed(speed)?;
+        self.pin.set_pull(pull)?;
+        self.pin.set_mode(PinMode::Output)
+    }
+
+    #[inline]
+    pub fn set_as_analog(&mut self) -> GpioResult<()> {
+        self.pin.set_mode(PinMode::Analog)?;
+        self.pin.set_pull(Pull::None)
     }
 }
 
-impl Drop for Analog {
+impl Drop for Flex {
     #[inline]
     fn drop(&mut self) {
-        self.pin.set_mode(PinMode::Analog);
-        self.pin.set_output_type(OutputType::PushPull);
-        self.pin.set_speed(Speed::Low);
-        self.pin.set_pull(Pull::None);
-        self.pin.set_alternate_function(0);
+        self.pin.reset();
     }
 }
 
@@ -389,19 +606,22 @@ pub enum Pull {
     None = 0b00,
     Up = 0b01,
     Down = 0b10,
+    /// Reserved PUPDR value, only read back from a misconfigured pin
+    Reserved = 0b11,
 }
 
 impl Pull {
     pub fn from_bits(value: u32) -> Self {
-        match value {
+        match value & 0b11 {
             0b00 => Self::None,
             0b01 => Self::Up,
             0b10 => Self::Down,
-            _ => panic!(),
+            _ => Self::Reserved,
         }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PinConfig {
     Input(Pull),
     Output(Speed),
@@ -410,6 +630,28 @@ pub enum PinConfig {
     Analog,
 }
 
+impl PinConfig {
+    /// Decodes the MODER, OTYPER, OSPEEDR, PUPDR and AFR fields of one pin.
+    ///
+    /// Fields that do not apply to the mode are dropped, e.g. the pull of a push-pull
+    /// output, as [`Port::init_pins`] would not write them either.
+    pub fn from_bits(mode: u32, otype: u32, speed: u32, pull: u32, func: u32) -> Self {
+        let otype = OutputType::from_bits(otype);
+        let speed = Speed::from_bits(speed);
+        let pull = Pull::from_bits(pull);
+
+        match PinMode::from(mode) {
+            PinMode::Input => Self::Input(pull),
+            PinMode::Output => match otype {
+                OutputType::PushPull => Self::Output(speed),
+                OutputType::OpenDrain => Self::OpenDrain(speed, pull),
+            },
+            PinMode::Alternate => Self::Alternate(otype, speed, pull, func as u8),
+            PinMode::Analog => Self::Analog,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Level {
     Low = 0b0,
@@ -443,3 +685,187 @@ impl From<bool> for Level {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use core::{ mem::size_of, ptr::{ addr_of, addr_of_mut } };
+
+    use super::*;
+
+    /// RAM stand-in for a GPIO register block. BSRR is plain memory here, so writes to it
+    /// stay visible instead of reaching ODR.
+    #[repr(C, align(4))]
+    struct Regs([u32; 10]);
+
+    const _: () = assert!(size_of::<Regs>() == size_of::<pac::gpioa::RegisterBlock>());
+
+    const MODER: usize = 0;
+    const OTYPER: usize = 1;
+    const OSPEEDR: usize = 2;
+    const PUPDR: usize = 3;
+    const BSRR: usize = 6;
+    const LCKR: usize = 7;
+
+    fn fake_pin(regs: *mut Regs, pin: u8) -> Pin {
+        Pin { port: unsafe { &*(regs as *const Port) }, pin }
+    }
+
+    fn reg(regs: *const Regs, index: usize) -> u32 {
+        unsafe { addr_of!((*regs).0[index]).read_volatile() }
+    }
+
+    fn set_reg(regs: *mut Regs, index: usize, value: u32) {
+        unsafe { addr_of_mut!((*regs).0[index]).write_volatile(value) }
+    }
+
+    #[test]
+    fn with_level_sets_data_register() {
+        static mut REGS: Regs = Regs([0; 10]);
+        let regs = addr_of_mut!(REGS);
+
+        let output = Output::with_level(fake_pin(regs, 2), Speed::Medium, Level::High).ok().unwrap();
+        assert_eq!(reg(regs, BSRR), 1 << 2);
+        assert_eq!(reg(regs, MODER), 0b01 << 4);
+        assert_eq!(reg(regs, OSPEEDR), 0b01 << 4);
+
+        let pin = output.into_inner();
+        assert_eq!(reg(regs, MODER), 0b01 << 4);
+
+        let od = OpenDrain::with_level(pin, Speed::Low, Pull::Up, Level::Low).ok().unwrap();
+        assert_eq!(reg(regs, BSRR), 1 << (2 + 16));
+        assert_eq!(reg(regs, OTYPER), 1 << 2);
+        assert_eq!(reg(regs, PUPDR), 0b01 << 4);
+
+        drop(od);
+        assert_eq!(reg(regs, MODER), 0b11 << 4);
+    }
+
+    #[test]
+    fn direction_changes_skip_analog_mode() {
+        static mut REGS: Regs = Regs([0; 10]);
+        let regs = addr_of_mut!(REGS);
+
+        let input = Input::new(fake_pin(regs, 12), Pull::Down).ok().unwrap();
+        assert_eq!(reg(regs, MODER), 0);
+        assert_eq!(reg(regs, PUPDR), 0b10 << 24);
+
+        let output = input.into_output(Speed::VeryHigh, Level::High).ok().unwrap();
+        assert_eq!(reg(regs, BSRR), 1 << 12);
+        assert_eq!(reg(regs, MODER), 0b01 << 24);
+        assert_eq!(reg(regs, PUPDR), 0);
+
+        let od = OpenDrain::new(output.into_inner(), Speed::Low, Pull::None).ok().unwrap();
+        let input = od.into_input(Pull::Up).ok().unwrap();
+        assert_eq!(reg(regs, MODER), 0);
+        assert_eq!(reg(regs, PUPDR), 0b01 << 24);
+
+        let output = Output::new(input.into_inner(), Speed::Low).ok().unwrap();
+        let input = output.into_input(Pull::None).ok().unwrap();
+        assert_eq!(reg(regs, MODER), 0);
+        assert_eq!(reg(regs, PUPDR), 0);
+
+        drop(input);
+        assert_eq!(reg(regs, MODER), 0b11 << 24);
+    }
+
+    #[test]
+    fn locked_pin_is_taken_over_with_its_configuration() {
+        static mut REGS: Regs = Regs([0; 10]);
+        let regs = addr_of_mut!(REGS);
+        set_reg(regs, MODER, 0b01 << 8);
+        set_reg(regs, OSPEEDR, 0b10 << 8);
+        set_reg(regs, LCKR, (1 << 16) | (1 << 4));
+
+        let Err((pin, err)) = Output::new(fake_pin(regs, 4), Speed::Low) else { panic!() };
+        assert_eq!(err, Error::Locked);
+
+        let Err((pin, err)) = Input::new(pin, Pull::None) else { panic!() };
+        assert_eq!(err, Error::Locked);
+        assert_eq!(reg(regs, MODER), 0b01 << 8);
+
+        let output = Output::with_level(pin, Speed::High, Level::High).ok().unwrap();
+        assert_eq!(reg(regs, BSRR), 1 << 4);
+
+        let Err((output, err)) = output.into_input(Pull::Up) else { panic!() };
+        assert_eq!(err, Error::Locked);
+        assert_eq!(reg(regs, PUPDR), 0);
+
+        drop(output);
+        assert_eq!(reg(regs, MODER), 0b01 << 8);
+    }
+
+    #[test]
+    fn flex_switches_direction_in_place() {
+        static mut REGS: Regs = Regs([0; 10]);
+        let regs = addr_of_mut!(REGS);
+        let mut flex = Flex::new(fake_pin(regs, 5));
+
+        flex.set_as_output(Speed::High).unwrap();
+        assert_eq!(reg(regs, MODER), 0b01 << 10);
+        assert_eq!(reg(regs, OSPEEDR), 0b10 << 10);
+        assert_eq!(reg(regs, OTYPER), 0);
+
+        flex.set_as_input(Pull::Up).unwrap();
+        assert_eq!(reg(regs, MODER), 0);
+        assert_eq!(reg(regs, PUPDR), 0b01 << 10);
+
+        flex.set_as_open_drain(Speed::Low, Pull::None).unwrap();
+        assert_eq!(reg(regs, MODER), 0b01 << 10);
+        assert_eq!(reg(regs, OTYPER), 1 << 5);
+        assert_eq!(reg(regs, PUPDR), 0);
+
+        drop(flex);
+        assert_eq!(reg(regs, MODER), 0b11 << 10);
+        assert_eq!(reg(regs, OTYPER), 0);
+    }
+
+    #[test]
+    fn flex_output_with_level_sets_data_register() {
+        static mut REGS: Regs = Regs([0; 10]);
+        let regs = addr_of_mut!(REGS);
+        let mut flex = Flex::new(fake_pin(regs, 3));
+
+        flex.set_as_output_with_level(Speed::Low, Level::Low).ok().unwrap();
+        assert_eq!(reg(regs, BSRR), 1 << (3 + 16));
+        assert_eq!(reg(regs, MODER), 0b01 << 6);
+
+        flex.set_as_input(Pull::None).unwrap();
+        flex.set_as_output_with_level(Speed::Low, Level::High).ok().unwrap();
+        assert_eq!(reg(regs, BSRR), 1 << 3);
+        assert_eq!(reg(regs, MODER), 0b01 << 6);
+    }
+
+    #[test]
+    fn locked_flex_is_left_alone() {
+        static mut REGS: Regs = Regs([0; 10]);
+        let regs = addr_of_mut!(REGS);
+        set_reg(regs, MODER, 0b01 << 14);
+        set_reg(regs, LCKR, (1 << 16) | (1 << 7));
+        let mut flex = Flex::new(fake_pin(regs, 7));
+
+        assert_eq!(flex.set_as_input(Pull::Up), Err(Error::Locked));
+        assert_eq!(flex.set_as_analog(), Err(Error::Locked));
+        drop(flex);
+        assert_eq!(reg(regs, MODER), 0b01 << 14);
+        assert_eq!(reg(regs, PUPDR), 0);
+    }
+
+    #[test]
+    fn pin_config_from_bits() {
+        assert_eq!(PinConfig::from_bits(0b00, 0, 0b11, 0b01, 0), PinConfig::Input(Pull::Up));
+        assert_eq!(PinConfig::from_bits(0b01, 0, 0b10, 0b01, 0), PinConfig::Output(Speed::High));
+        assert_eq!(
+            PinConfig::from_bits(0b01, 1, 0b00, 0b01, 0),
+            PinConfig::OpenDrain(Speed::Low, Pull::Up)
+        );
+        assert_eq!(
+            PinConfig::from_bits(0b10, 1, 0b11, 0b00, 4),
+            PinConfig::Alternate(OutputType::OpenDrain, Speed::VeryHigh, Pull::None, 4)
+        );
+        assert_eq!(PinConfig::from_bits(0b11, 0, 0b00, 0b00, 0), PinConfig::Analog);
+        assert_eq!(
+            PinConfig::from_bits(0b00, 0, 0b00, 0b11, 0),
+            PinConfig::Input(Pull::Reserved)
+        );
+    }
+}
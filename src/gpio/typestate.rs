@@ -0,0 +1,397 @@
+//! Pins with their port, number and mode encoded in the type.
+//!
+//! Each pin is handed out once by `GPIOx::split`, so two drivers can not own the same
+//! pin, and a pin can only be used in the ways its mode allows.
+//!
+//! `split` consumes the `GPIOx` handle, so the mask-wide [`Port`] methods it derefs to
+//! are no longer reachable once the pins are handed out. To keep a pin locked, configure
+//! and lock it through the handle first: a conversion to the same configuration then
+//! succeeds as it changes nothing, any other one hands the pin back with
+//! [`Error::Locked`](super::Error::Locked).
+//!
+//! ```ignore
+//! p.GPIOG.init_output_pins(1 << 13, Speed::Low);
+//! p.GPIOG.lock_pins(1 << 13)?;
+//! let gpiog = p.GPIOG.split(&mut rcc);
+//! let mut led = gpiog.p13.into_push_pull_output().ok().unwrap();
+//! let sck = gpiog.p7.into_alternate::<5>().ok().unwrap();
+//! ```
+
+use core::{ convert::Infallible, marker::PhantomData };
+
+use embedded_hal::digital::{ ErrorType, InputPin, OutputPin, StatefulOutputPin };
+
+use crate::pac;
+
+use super::{
+    pin::{ self, OutputType, Pin, PinMode, Pull, Speed },
+    Converted,
+    Port,
+    Result as GpioResult,
+};
+
+/// Type-level pin modes.
+pub mod mode {
+    /// Mode left by reset or by a bootloader, configure the pin before use
+    pub struct Unknown;
+
+    pub struct Input;
+
+    pub struct Output;
+
+    pub struct OpenDrain;
+
+    /// Push-pull alternate function `AF`
+    pub struct Alternate<const AF: u8>;
+
+    /// Open-drain alternate function `AF`, as required by I2C
+    pub struct AlternateOpenDrain<const AF: u8>;
+
+    pub struct Analog;
+}
+
+use self::mode::{ Alternate, AlternateOpenDrain, Analog, Input, OpenDrain, Output, Unknown };
+
+/// Pin `N` of port `P` in mode `MODE`.
+pub struct PXn<const P: char, const N: u8, MODE> {
+    _mode: PhantomData<MODE>,
+}
+
+pub type PA<const N: u8, MODE = Unknown> = PXn<'A', N, MODE>;
+pub type PB<const N: u8, MODE = Unknown> = PXn<'B', N, MODE>;
+pub type PC<const N: u8, MODE = Unknown> = PXn<'C', N, MODE>;
+pub type PD<const N: u8, MODE = Unknown> = PXn<'D', N, MODE>;
+pub type PE<const N: u8, MODE = Unknown> = PXn<'E', N, MODE>;
+pub type PF<const N: u8, MODE = Unknown> = PXn<'F', N, MODE>;
+pub type PG<const N: u8, MODE = Unknown> = PXn<'G', N, MODE>;
+pub type PH<const N: u8, MODE = Unknown> = PXn<'H', N, MODE>;
+pub type PI<const N: u8, MODE = Unknown> = PXn<'I', N, MODE>;
+pub type PJ<const N: u8, MODE = Unknown> = PXn<'J', N, MODE>;
+pub type PK<const N: u8, MODE = Unknown> = PXn<'K', N, MODE>;
+
+const fn port_ptr(port: char) -> *const Port {
+    match port {
+        'A' => pac::GPIOA::PTR as _,
+        'B' => pac::GPIOB::PTR as _,
+        'C' => pac::GPIOC::PTR as _,
+        'D' => pac::GPIOD::PTR as _,
+        'E' => pac::GPIOE::PTR as _,
+        'F' => pac::GPIOF::PTR as _,
+        'G' => pac::GPIOG::PTR as _,
+        'H' => pac::GPIOH::PTR as _,
+        'I' => pac::GPIOI::PTR as _,
+        'J' => pac::GPIOJ::PTR as _,
+        'K' => pac::GPIOK::PTR as _,
+        _ => panic!(),
+    }
+}
+
+impl<const P: char, const N: u8, MODE> PXn<P, N, MODE> {
+    const fn new() -> Self {
+        Self { _mode: PhantomData }
+    }
+
+    #[inline]
+    fn port(&self) -> &'static Port {
+        unsafe { &*port_ptr(P) }
+    }
+
+    #[inline]
+    fn pin(&self) -> Pin {
+        self.port().pin(N)
+    }
+
+    /// Port letter and pin number.
+    #[inline]
+    pub const fn id(&self) -> (char, u8) {
+        (P, N)
+    }
+
+    /// Gives up the compile-time mode, the returned [`Pin`] can be reconfigured freely.
+    #[inline]
+    pub fn into_pin(self) -> Pin {
+        self.pin()
+    }
+
+    /// Gives up the compile-time mode for a pin whose direction changes at runtime.
+    #[inline]
+    pub fn into_flex(self) -> pin::Flex {
+        pin::Flex::new(self.pin())
+    }
+
+    #[inline]
+    pub fn into_floating_input(self) -> Converted<Self, PXn<P, N, Input>> {
+        self.into_input(Pull::None)
+    }
+
+    #[inline]
+    pub fn into_pull_up_input(self) -> Converted<Self, PXn<P, N, Input>> {
+        self.into_input(Pull::Up)
+    }
+
+    #[inline]
+    pub fn into_pull_down_input(self) -> Converted<Self, PXn<P, N, Input>> {
+        self.into_input(Pull::Down)
+    }
+
+    #[inline]
+    pub fn into_push_pull_output(self) -> Converted<Self, PXn<P, N, Output>> {
+        self.convert(|pin| {
+            pin.set_output_type(OutputType::PushPull)?;
+            pin.set_pull(Pull::None)?;
+            pin.set_mode(PinMode::Output)
+        })
+    }
+
+    #[inline]
+    pub fn into_open_drain_output(self, pull: Pull) -> Converted<Self, PXn<P, N, OpenDrain>> {
+        self.convert(|pin| {
+            pin.set_output_type(OutputType::OpenDrain)?;
+            pin.set_pull(pull)?;
+            pin.set_mode(PinMode::Output)
+        })
+    }
+
+    /// Push-pull alternate function `AF`, 0 to 15.
+    #[inline]
+    pub fn into_alternate<const AF: u8>(self) -> Converted<Self, PXn<P, N, Alternate<AF>>> {
+        const { assert!(AF < 16, "alternate function out of range") };
+        self.into_alternate_with(AF, OutputType::PushPull, Pull::None)
+    }
+
+    /// Open-drain alternate function `AF`, e.g. for I2C.
+    #[inline]
+    pub fn into_alternate_open_drain<const AF: u8>(
+        self,
+        pull: Pull
+    ) -> Converted<Self, PXn<P, N, AlternateOpenDrain<AF>>> {
+        const { assert!(AF < 16, "alternate function out of range") };
+        self.into_alternate_with(AF, OutputType::OpenDrain, pull)
+    }
+
+    #[inline]
+    pub fn into_analog(self) -> Converted<Self, PXn<P, N, Analog>> {
+        self.convert(|pin| {
+            pin.set_mode(PinMode::Analog)?;
+            pin.set_pull(Pull::None)
+        })
+    }
+
+    #[inline]
+    fn into_input(self, pull: Pull) -> Converted<Self, PXn<P, N, Input>> {
+        self.convert(|pin| {
+            pin.set_pull(pull)?;
+            pin.set_mode(PinMode::Input)
+        })
+    }
+
+    #[inline]
+    fn into_alternate_with<AM>(
+        self,
+        af: u8,
+        otype: OutputType,
+        pull: Pull
+    ) -> Converted<Self, PXn<P, N, AM>> {
+        self.convert(|pin| {
+            pin.set_output_type(otype)?;
+            pin.set_pull(pull)?;
+            pin.set_alternate_function(af)?;
+            pin.set_mode(PinMode::Alternate)
+        })
+    }
+
+    #[inline]
+    fn convert<M>(
+        self,
+        f: impl FnOnce(&mut Pin) -> GpioResult<()>
+    ) -> Converted<Self, PXn<P, N, M>> {
+        match self.pin().configure(f) {
+            Ok(_) => Ok(PXn::new()),
+            Err((_, err)) => Err((self, err)),
+        }
+    }
+}
+
+macro_rules! speed {
+    ($($mode:ty $(, $AF:ident)?;)+) => {
+        $(
+            impl<const P: char, const N: u8 $(, const $AF: u8)?> PXn<P, N, $mode> {
+                #[inline]
+                pub fn set_speed(&mut self, speed: Speed) -> GpioResult<()> {
+                    self.pin().set_speed(speed)
+                }
+            }
+        )+
+    };
+}
+
+speed! {
+    Output;
+    OpenDrain;
+    Alternate<AF>, AF;
+    AlternateOpenDrain<AF>, AF;
+}
+
+impl<const P: char, const N: u8, MODE> ErrorType for PXn<P, N, MODE> {
+    type Error = Infallible;
+}
+
+impl<const P: char, const N: u8> InputPin for PXn<P, N, Input> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.pin().is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.pin().is_low()
+    }
+}
+
+impl<const P: char, const N: u8> InputPin for PXn<P, N, OpenDrain> {
+    fn is_high(&mut self) -> Result<bool, Self::Error> {
+        self.pin().is_high()
+    }
+
+    fn is_low(&mut self) -> Result<bool, Self::Error> {
+        self.pin().is_low()
+    }
+}
+
+impl<const P: char, const N: u8> OutputPin for PXn<P, N, Output> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.pin().set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.pin().set_high()
+    }
+}
+
+impl<const P: char, const N: u8> StatefulOutputPin for PXn<P, N, Output> {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        self.pin().is_set_high()
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        self.pin().is_set_low()
+    }
+}
+
+impl<const P: char, const N: u8> OutputPin for PXn<P, N, OpenDrain> {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.pin().set_low()
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.pin().set_high()
+    }
+}
+
+impl<const P: char, const N: u8> StatefulOutputPin for PXn<P, N, OpenDrain> {
+    fn is_set_high(&mut self) -> Result<bool, Self::Error> {
+        self.pin().is_set_high()
+    }
+
+    fn is_set_low(&mut self) -> Result<bool, Self::Error> {
+        self.pin().is_set_low()
+    }
+}
+
+impl<const P: char, const N: u8> From<PXn<P, N, Input>> for pin::Input {
+    #[inline]
+    fn from(value: PXn<P, N, Input>) -> Self {
+        Self { pin: value.into_pin() }
+    }
+}
+
+impl<const P: char, const N: u8> From<PXn<P, N, Output>> for pin::Output {
+    #[inline]
+    fn from(value: PXn<P, N, Output>) -> Self {
+        Self { pin: value.into_pin() }
+    }
+}
+
+impl<const P: char, const N: u8> From<PXn<P, N, OpenDrain>> for pin::OpenDrain {
+    #[inline]
+    fn from(value: PXn<P, N, OpenDrain>) -> Self {
+        Self { pin: value.into_pin() }
+    }
+}
+
+impl<const P: char, const N: u8, const AF: u8> From<PXn<P, N, Alternate<AF>>> for pin::Alternate {
+    #[inline]
+    fn from(value: PXn<P, N, Alternate<AF>>) -> Self {
+        Self { pin: value.into_pin() }
+    }
+}
+
+impl<const P: char, const N: u8, const AF: u8> From<PXn<P, N, AlternateOpenDrain<AF>>>
+for pin::Alternate {
+    #[inline]
+    fn from(value: PXn<P, N, AlternateOpenDrain<AF>>) -> Self {
+        Self { pin: value.into_pin() }
+    }
+}
+
+impl<const P: char, const N: u8> From<PXn<P, N, Analog>> for pin::Analog {
+    #[inline]
+    fn from(value: PXn<P, N, Analog>) -> Self {
+        Self { pin: value.into_pin() }
+    }
+}
+
+/// The 16 pins of port `P`, returned by `GPIOx::split`.
+pub struct Parts<const P: char> {
+    pub p0: PXn<P, 0, Unknown>,
+    pub p1: PXn<P, 1, Unknown>,
+    pub p2: PXn<P, 2, Unknown>,
+    pub p3: PXn<P, 3, Unknown>,
+    pub p4: PXn<P, 4, Unknown>,
+    pub p5: PXn<P, 5, Unknown>,
+    pub p6: PXn<P, 6, Unknown>,
+    pub p7: PXn<P, 7, Unknown>,
+    pub p8: PXn<P, 8, Unknown>,
+    pub p9: PXn<P, 9, Unknown>,
+    pub p10: PXn<P, 10, Unknown>,
+    pub p11: PXn<P, 11, Unknown>,
+    pub p12: PXn<P, 12, Unknown>,
+    pub p13: PXn<P, 13, Unknown>,
+    pub p14: PXn<P, 14, Unknown>,
+    pub p15: PXn<P, 15, Unknown>,
+}
+
+impl<const P: char> Parts<P> {
+    pub(crate) const fn new() -> Self {
+        Self {
+            p0: PXn::new(),
+            p1: PXn::new(),
+            p2: PXn::new(),
+            p3: PXn::new(),
+            p4: PXn::new(),
+            p5: PXn::new(),
+            p6: PXn::new(),
+            p7: PXn::new(),
+            p8: PXn::new(),
+            p9: PXn::new(),
+            p10: PXn::new(),
+            p11: PXn::new(),
+            p12: PXn::new(),
+            p13: PXn::new(),
+            p14: PXn::new(),
+            p15: PXn::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::mem::size_of;
+
+    use super::*;
+
+    #[test]
+    fn pins_are_zero_sized() {
+        assert_eq!(size_of::<PA<8, Alternate<0>>>(), 0);
+        assert_eq!(size_of::<Parts<'G'>>(), 0);
+        assert_eq!(PC::<13>::new().id(), ('C', 13));
+    }
+}
@@ -1,97 +1,96 @@
 #![allow(dead_code)]
 
-use core::{ fmt, ops::Deref, ptr::addr_of };
+use core::{ fmt, ops::Deref };
 
-use crate::{ pac, rcc::RCC, Peripheral, PeripheralRef };
+use crate::{ pac, rcc::{ Clocks, PeripheralClock, RCC }, time::Hertz, Peripheral };
 
-pub struct I2C(pac::i2c1::RegisterBlock);
+/// I2C driver owning the peripheral handle `I` and its SCL and SDA pins.
+pub struct I2C<I: Instance, PINS> {
+    i2c: I,
+    pins: PINS,
+}
 
-impl Deref for I2C {
+impl<I: Instance, PINS> Deref for I2C<I, PINS> {
     type Target = pac::i2c1::RegisterBlock;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        unsafe { &*I::PTR }
     }
 }
 
-pub struct I2C1;
-
-impl PeripheralRef for I2C1 {
-    type Output = I2C;
+/// Owned handle of I2C1, obtained from [`Peripherals`](crate::Peripherals).
+pub struct I2C1(pub(crate) ());
 
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::I2C1::PTR as *mut Self::Output).as_mut().unwrap() }
-    }
+impl Instance for I2C1 {
+    const PTR: *const pac::i2c1::RegisterBlock = pac::I2C1::PTR;
 }
 
-pub struct I2C2;
+impl Peripheral for I2C1 {
+    const CLOCK: PeripheralClock = PeripheralClock::I2C1;
+}
 
-impl PeripheralRef for I2C2 {
-    type Output = I2C;
+/// Owned handle of I2C2, obtained from [`Peripherals`](crate::Peripherals).
+pub struct I2C2(pub(crate) ());
 
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::I2C2::PTR as *mut Self::Output).as_mut().unwrap() }
-    }
+impl Instance for I2C2 {
+    const PTR: *const pac::i2c1::RegisterBlock = pac::I2C2::PTR;
 }
 
-pub struct I2C3;
+impl Peripheral for I2C2 {
+    const CLOCK: PeripheralClock = PeripheralClock::I2C2;
+}
 
-impl PeripheralRef for I2C3 {
-    type Output = I2C;
+/// Owned handle of I2C3, obtained from [`Peripherals`](crate::Peripherals).
+pub struct I2C3(pub(crate) ());
 
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::I2C3::PTR as *mut Self::Output).as_mut().unwrap() }
-    }
+impl Instance for I2C3 {
+    const PTR: *const pac::i2c1::RegisterBlock = pac::I2C3::PTR;
 }
 
-impl Peripheral for I2C {
-    fn reset(&mut self) {
-        let rcc = RCC::take();
-        let ptr = addr_of!(self.0);
-
-        match ptr {
-            pac::I2C1::PTR => {
-                rcc.apb1rstr().modify(|_, w| w.i2c1rst().set_bit());
-                rcc.apb1rstr().modify(|_, w| w.i2c1rst().clear_bit());
-            }
-            pac::I2C2::PTR => {
-                rcc.apb1rstr().modify(|_, w| w.i2c2rst().set_bit());
-                rcc.apb1rstr().modify(|_, w| w.i2c2rst().clear_bit());
-            }
-            pac::I2C3::PTR => {
-                rcc.apb1rstr().modify(|_, w| w.i2c3rst().set_bit());
-                rcc.apb1rstr().modify(|_, w| w.i2c3rst().clear_bit());
-            }
-            _ => panic!(),
-        }
-    }
+impl Peripheral for I2C3 {
+    const CLOCK: PeripheralClock = PeripheralClock::I2C3;
+}
 
-    fn enable_clock(&mut self) {
-        let rcc = RCC::take();
-        let ptr = addr_of!(self.0);
+/// I2C1 to I2C3, used to match pins with the peripheral they are wired to.
+pub trait Instance: Peripheral {
+    const PTR: *const pac::i2c1::RegisterBlock;
+}
 
-        match ptr {
-            pac::I2C1::PTR => rcc.apb1enr().modify(|_, w| w.i2c1en().set_bit()),
-            pac::I2C2::PTR => rcc.apb1enr().modify(|_, w| w.i2c2en().set_bit()),
-            pac::I2C3::PTR => rcc.apb1enr().modify(|_, w| w.i2c3en().set_bit()),
-            _ => panic!(),
-        }
+/// Pins able to carry the SCL signal of `I2C`, see [`crate::gpio::alt`].
+///
+/// Only implemented for pins configured with `into_alternate_open_drain`.
+pub trait SclPin<I2C> {}
+
+pub trait SdaPin<I2C> {}
+
+impl<I: Instance, SCL: SclPin<I>, SDA: SdaPin<I>> I2C<I, (SCL, SDA)> {
+    /// Takes ownership of `i2c` and configures it with `pins` as SCL and SDA.
+    pub fn new(
+        mut i2c: I,
+        rcc: &mut RCC,
+        clocks: &Clocks,
+        pins: (SCL, SDA),
+        mode: I2CMode,
+        speed_mode: SpeedMode,
+        scl_freq: Hertz
+    ) -> Result<Self> {
+        i2c.enable_clock(rcc);
+        i2c.reset(rcc);
+
+        let mut i2c = Self { i2c, pins };
+        i2c.configure(clocks, mode, speed_mode, scl_freq)?;
+
+        Ok(i2c)
     }
+}
 
-    fn disable_clock(&mut self) {
-        let rcc = RCC::take();
-        let ptr = addr_of!(self.0);
-
-        match ptr {
-            pac::I2C1::PTR => rcc.apb1enr().modify(|_, w| w.i2c1en().clear_bit()),
-            pac::I2C2::PTR => rcc.apb1enr().modify(|_, w| w.i2c2en().clear_bit()),
-            pac::I2C3::PTR => rcc.apb1enr().modify(|_, w| w.i2c3en().clear_bit()),
-            _ => panic!(),
-        }
+impl<I: Instance, PINS> I2C<I, PINS> {
+    /// Disables the peripheral and gives its handle and pins back.
+    pub fn release(mut self) -> (I, PINS) {
+        self.disable();
+        (self.i2c, self.pins)
     }
-}
 
-impl I2C {
     pub fn enable(&mut self) {
         self.cr1().modify(|_, w| w.pe().set_bit())
     }
@@ -104,19 +103,26 @@ impl I2C {
         self.cr1().read().pe().bit()
     }
 
-    pub fn init(&mut self, mode: I2CMode, speed_mode: SpeedMode, scl_freq: u32) -> Result<()> {
+    fn configure(
+        &mut self,
+        clocks: &Clocks,
+        mode: I2CMode,
+        speed_mode: SpeedMode,
+        scl_freq: Hertz
+    ) -> Result<()> {
         self.disable();
 
-        if scl_freq > 100_000 && speed_mode == SpeedMode::StandardMode {
+        if scl_freq > Hertz::kHz(100) && speed_mode == SpeedMode::StandardMode {
             return Err(Error::InitError("Frequency is too high"));
         }
 
-        if scl_freq <= 100_000 && speed_mode != SpeedMode::StandardMode {
+        if scl_freq <= Hertz::kHz(100) && speed_mode != SpeedMode::StandardMode {
             return Err(Error::InitError("Frequency is too low"));
         }
 
         // calculate CCR
-        let f_pclk1 = RCC::take().pclk1_freq();
+        let f_pclk1 = clocks.pclk1().raw();
+        let scl_freq = scl_freq.raw();
 
         let (ccr, trise) = match speed_mode {
             SpeedMode::StandardMode => (f_pclk1 / scl_freq / 2, f_pclk1 / 1_000_000 + 1),
@@ -280,7 +286,9 @@ impl I2C {
 
     #[inline]
     fn generate_stop_condition_sync(&mut self) -> Result<()> {
-        Ok(self.generate_stop_condition_async())
+        self.generate_stop_condition_async();
+
+        Ok(())
     }
 
     #[inline]
@@ -350,7 +358,7 @@ impl I2C {
 pub struct I2CConfig {
     pub mode: I2CMode,
     pub speed_mode: SpeedMode,
-    pub scl_freq: u32,
+    pub scl_freq: Hertz,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -4,23 +4,138 @@ pub mod gpio;
 pub mod spi;
 pub mod ltdc;
 pub mod rcc;
+pub mod flash;
+pub mod pwr;
 pub mod i2c;
+pub mod time;
+pub mod exti;
 
 pub extern crate stm32f4_staging;
 pub extern crate embedded_hal;
+pub extern crate embedded_hal_async;
+pub extern crate fugit;
 
 pub use stm32f4_staging::stm32f429 as pac;
 
+use core::sync::atomic::{ AtomicBool, Ordering };
+
 pub trait Peripheral {
-    fn enable_clock(&mut self);
-    fn disable_clock(&mut self);
-    fn reset(&mut self);
+    /// Position of this peripheral in the RCC clock gating registers.
+    const CLOCK: rcc::PeripheralClock;
+
+    #[inline]
+    fn enable_clock(&mut self, rcc: &mut rcc::RCC) {
+        rcc.enable_peripheral_clock(Self::CLOCK);
+    }
+
+    #[inline]
+    fn disable_clock(&mut self, rcc: &mut rcc::RCC) {
+        rcc.disable_peripheral_clock(Self::CLOCK);
+    }
+
+    #[inline]
+    fn is_clock_enabled(&self, rcc: &rcc::RCC) -> bool {
+        rcc.is_peripheral_clock_enabled(Self::CLOCK)
+    }
+
+    /// Keeps the peripheral clocked while the core is in Sleep mode.
+    #[inline]
+    fn enable_clock_in_sleep(&mut self, rcc: &mut rcc::RCC) {
+        rcc.enable_peripheral_clock_in_sleep(Self::CLOCK);
+    }
+
+    #[inline]
+    fn disable_clock_in_sleep(&mut self, rcc: &mut rcc::RCC) {
+        rcc.disable_peripheral_clock_in_sleep(Self::CLOCK);
+    }
+
+    #[inline]
+    fn reset(&mut self, rcc: &mut rcc::RCC) {
+        rcc.reset_peripheral(Self::CLOCK);
+    }
+}
+
+/// Every peripheral supported by the HAL, handed out once by [`Peripherals::take`].
+///
+/// Each field is a zero-sized owned handle, drivers consume or borrow it.
+#[allow(non_snake_case)]
+pub struct Peripherals {
+    pub GPIOA: gpio::GPIOA,
+    pub GPIOB: gpio::GPIOB,
+    pub GPIOC: gpio::GPIOC,
+    pub GPIOD: gpio::GPIOD,
+    pub GPIOE: gpio::GPIOE,
+    pub GPIOF: gpio::GPIOF,
+    pub GPIOG: gpio::GPIOG,
+    pub GPIOH: gpio::GPIOH,
+    pub GPIOI: gpio::GPIOI,
+    pub GPIOJ: gpio::GPIOJ,
+    pub GPIOK: gpio::GPIOK,
+    pub SPI1: spi::SPI1,
+    pub SPI2: spi::SPI2,
+    pub SPI3: spi::SPI3,
+    pub SPI4: spi::SPI4,
+    pub SPI5: spi::SPI5,
+    pub SPI6: spi::SPI6,
+    pub I2C1: i2c::I2C1,
+    pub I2C2: i2c::I2C2,
+    pub I2C3: i2c::I2C3,
+    pub LTDC: ltdc::LTDC,
+    pub RCC: rcc::RCC,
+    pub FLASH: flash::FLASH,
+    pub PWR: pwr::PWR,
+    pub EXTI: exti::EXTI,
 }
 
-pub trait PeripheralRef {
-    type Output;
+static TAKEN: AtomicBool = AtomicBool::new(false);
 
-    fn take() -> &'static mut Self::Output;
+impl Peripherals {
+    /// Returns all peripherals on the first call and `None` afterwards.
+    #[inline]
+    pub fn take() -> Option<Self> {
+        if TAKEN.swap(true, Ordering::AcqRel) {
+            None
+        } else {
+            Some(unsafe { Self::steal() })
+        }
+    }
+
+    /// Returns all peripherals, even if they were already taken.
+    ///
+    /// # Safety
+    ///
+    /// Every previously obtained peripheral handle must be dropped.
+    pub unsafe fn steal() -> Self {
+        TAKEN.store(true, Ordering::Release);
+
+        Self {
+            GPIOA: gpio::GPIOA(()),
+            GPIOB: gpio::GPIOB(()),
+            GPIOC: gpio::GPIOC(()),
+            GPIOD: gpio::GPIOD(()),
+            GPIOE: gpio::GPIOE(()),
+            GPIOF: gpio::GPIOF(()),
+            GPIOG: gpio::GPIOG(()),
+            GPIOH: gpio::GPIOH(()),
+            GPIOI: gpio::GPIOI(()),
+            GPIOJ: gpio::GPIOJ(()),
+            GPIOK: gpio::GPIOK(()),
+            SPI1: spi::SPI1(()),
+            SPI2: spi::SPI2(()),
+            SPI3: spi::SPI3(()),
+            SPI4: spi::SPI4(()),
+            SPI5: spi::SPI5(()),
+            SPI6: spi::SPI6(()),
+            I2C1: i2c::I2C1(()),
+            I2C2: i2c::I2C2(()),
+            I2C3: i2c::I2C3(()),
+            LTDC: ltdc::LTDC(()),
+            RCC: rcc::RCC(()),
+            FLASH: flash::FLASH(()),
+            PWR: pwr::PWR(()),
+            EXTI: exti::EXTI(()),
+        }
+    }
 }
 
 pub type InterruptHandler = fn();
@@ -29,13 +144,12 @@ pub type InterruptHandler = fn();
 mod tests {
     use embedded_hal::digital::OutputPin;
 
-    use self::gpio::GPIOA;
-
     use super::*;
 
     #[test]
     fn it_works() {
-        let gpioa = gpio::GPIOA::take();
+        let p = Peripherals::take().unwrap();
+        let gpioa = p.GPIOA;
 
         let mut led1 = gpioa.pin(0);
         let mut led2 = gpioa.pin(1);
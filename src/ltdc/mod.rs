@@ -1,64 +1,50 @@
-use core::ops::Deref;
+use core::{ fmt, ops::Deref };
 
-use crate::{ pac::{ self, interrupt }, rcc::RCC, InterruptHandler, Peripheral, PeripheralRef };
+use crate::{
+    pac::{ self, interrupt },
+    rcc::{ Clocks, PeripheralClock, RCC },
+    InterruptHandler,
+    Peripheral,
+};
 
-pub struct LTDC(pac::ltdc::RegisterBlock);
+/// Owned handle of the LCD-TFT controller, obtained from [`Peripherals`](crate::Peripherals).
+pub struct LTDC(pub(crate) ());
 
 impl Deref for LTDC {
     type Target = pac::ltdc::RegisterBlock;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
-    }
-}
-
-impl PeripheralRef for LTDC {
-    type Output = LTDC;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::LTDC::PTR as *mut Self::Output).as_mut().unwrap() }
+        unsafe { &*pac::LTDC::ptr() }
     }
 }
 
 impl Peripheral for LTDC {
-    fn enable_clock(&mut self) {
-        RCC::take()
-            .apb2enr()
-            .modify(|_, w| w.ltdcen().set_bit());
-    }
-
-    fn disable_clock(&mut self) {
-        RCC::take()
-            .apb2enr()
-            .modify(|_, w| w.ltdcen().clear_bit());
-    }
-
-    fn reset(&mut self) {
-        let rcc = RCC::take();
-        rcc.apb2rstr().modify(|_, w| w.ltdcrst().set_bit());
-        rcc.apb2rstr().modify(|_, w| w.ltdcrst().clear_bit());
-    }
+    const CLOCK: PeripheralClock = PeripheralClock::LTDC;
 }
 
 impl LTDC {
     pub fn enable(&mut self) {
-        self.0.gcr().modify(|_, w| w.ltdcen().set_bit());
+        self.gcr().modify(|_, w| w.ltdcen().set_bit());
     }
 
     pub fn disable(&mut self) {
-        self.0.gcr().modify(|_, w| w.ltdcen().clear_bit());
+        self.gcr().modify(|_, w| w.ltdcen().clear_bit());
     }
 
     pub fn is_enabled(&self) -> bool {
-        self.0.gcr().read().ltdcen().bit()
+        self.gcr().read().ltdcen().bit()
     }
 
-    pub fn init(&mut self, conf: LTDCConfig) {
+    pub fn init(&mut self, rcc: &mut RCC, clocks: &Clocks, conf: LTDCConfig) -> Result<()> {
+        if clocks.lcdclk().is_none() {
+            return Err(Error::InitError("PLLSAI must be configured before LTDC"));
+        }
+
         // enable clock
-        self.enable_clock();
-        self.reset();
+        self.enable_clock(rcc);
+        self.reset(rcc);
 
-        // configure required pixel clock (from arg)
+        // pixel clock is provided by PLLSAI, see `PLLSAIConfig::for_pixel_clock`
 
         // configure synchronous signals and clock polarity
         self.set_signal_polarity(
@@ -100,6 +86,8 @@ impl LTDC {
 
         // enable LCD-TFT controller
         self.enable();
+
+        Ok(())
     }
 
     pub fn set_register_reload_event_handler(&mut self, f: InterruptHandler) {
@@ -128,13 +116,13 @@ impl LTDC {
 
     pub fn set_background_color(&mut self, color: Color) {
         unsafe {
-            self.0.bccr().modify(|_, w| { w.bits(color.into_rgb888()) });
+            self.bccr().modify(|_, w| { w.bits(color.into_rgb888()) });
         }
     }
 
     pub fn enable_dither(&mut self, color: Color) {
         unsafe {
-            self.0.gcr().modify(|_, w| {
+            self.gcr().modify(|_, w| {
                 w.bits(
                     (((color.1 as u32) & 0b111) << 12) |
                         (((color.2 as u32) & 0b111) << 8) |
@@ -146,23 +134,24 @@ impl LTDC {
     }
 
     pub fn disable_dither(&mut self) {
-        self.0.gcr().modify(|_, w| { w.den().clear_bit() })
+        self.gcr().modify(|_, w| { w.den().clear_bit() })
     }
 
     pub fn layer1_enable(&mut self) {
-        self.0
+        self
             .layer1()
             .cr()
             .modify(|_, w| w.len().set_bit())
     }
 
     pub fn layer1_disable(&mut self) {
-        self.0
+        self
             .layer1()
             .cr()
             .modify(|_, w| w.len().clear_bit())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn layer1_configure(
         &mut self,
         x: u16,
@@ -174,7 +163,7 @@ impl LTDC {
         image_buffer_address: *const u8
     ) {
         self.layer_configure(
-            self.0.layer1(),
+            self.layer1(),
             x,
             y,
             width,
@@ -188,23 +177,24 @@ impl LTDC {
         self.layer1_enable();
 
         // reload shadow registers
-        self.0.srcr().modify(|_, w| w.imr().set_bit());
+        self.srcr().modify(|_, w| w.imr().set_bit());
     }
 
     pub fn layer2_enable(&mut self) {
-        self.0
+        self
             .layer2()
             .cr()
             .modify(|_, w| w.len().set_bit())
     }
 
     pub fn layer2_disable(&mut self) {
-        self.0
+        self
             .layer2()
             .cr()
             .modify(|_, w| w.len().clear_bit())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn layer2_configure(
         &mut self,
         x: u16,
@@ -216,7 +206,7 @@ impl LTDC {
         image_buffer_address: *const u8
     ) {
         self.layer_configure(
-            self.0.layer2(),
+            self.layer2(),
             x,
             y,
             width,
@@ -230,9 +220,10 @@ impl LTDC {
         self.layer2_enable();
 
         // reload shadow registers
-        self.0.srcr().modify(|_, w| w.imr().set_bit());
+        self.srcr().modify(|_, w| w.imr().set_bit());
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn layer_configure(
         &self,
         layer: &pac::ltdc::LAYER,
@@ -245,13 +236,13 @@ impl LTDC {
         image_buffer_address: *const u8
     ) {
         // configure layer window
-        let ahbp = self.0.bpcr().read().ahbp().bits();
+        let ahbp = self.bpcr().read().ahbp().bits();
         layer.whpcr().write(|w| {
             w.whstpos().set(ahbp + x + 1);
             w.whsppos().set(ahbp + x + width)
         });
 
-        let avbp = self.0.bpcr().read().avbp().bits();
+        let avbp = self.bpcr().read().avbp().bits();
         layer.wvpcr().write(|w| {
             w.wvstpos().set(avbp + y + 1);
             w.wvsppos().set(avbp + y + height)
@@ -287,6 +278,7 @@ impl LTDC {
         });
     }
 
+    #[allow(clippy::too_many_arguments)]
     fn set_sync_timings(
         &mut self,
         width: u16,
@@ -298,21 +290,21 @@ impl LTDC {
         hfp: u16,
         vfp: u16
     ) {
-        self.0.sscr().modify(|_, w| {
+        self.sscr().modify(|_, w| {
             w.hsw().set(hsw - 1);
             w.vsh().set(vsh - 1)
         });
-        self.0.bpcr().write(|w| {
-            w.ahbp().set(hsw + (hbp as u16) - 1);
-            w.avbp().set(vsh + (vbp as u16) - 1)
+        self.bpcr().write(|w| {
+            w.ahbp().set(hsw + hbp - 1);
+            w.avbp().set(vsh + vbp - 1)
         });
-        self.0.awcr().write(|w| {
-            w.aaw().set(hsw + (hbp as u16) + width - 1);
-            w.aah().set(vsh + (vbp as u16) + height - 1)
+        self.awcr().write(|w| {
+            w.aaw().set(hsw + hbp + width - 1);
+            w.aah().set(vsh + vbp + height - 1)
         });
-        self.0.twcr().write(|w| {
-            w.totalw().set(hsw + (hbp as u16) + width + (hfp as u16) - 1);
-            w.totalh().set(vsh + (vbp as u16) + height + (vfp as u16) - 1)
+        self.twcr().write(|w| {
+            w.totalw().set(hsw + hbp + width + hfp - 1);
+            w.totalh().set(vsh + vbp + height + vfp - 1)
         });
     }
 
@@ -323,7 +315,7 @@ impl LTDC {
         vspol: Polarity,
         hspol: Polarity
     ) {
-        self.0.gcr().modify(|_, w| {
+        self.gcr().modify(|_, w| {
             w.pcpol().bit(pcpol == PixelClockPolarity::Inverted);
             w.depol().bit(depol == Polarity::ActiveHigh);
             w.vspol().bit(vspol == Polarity::ActiveHigh);
@@ -474,7 +466,7 @@ const REGISTER_RELOAD_HANDLER: usize = 3;
 /// LTDC global event interrupt
 #[interrupt]
 fn LCD_TFT() {
-    let ltdc = LTDC::take();
+    let ltdc = unsafe { &*pac::LTDC::ptr() };
     if ltdc.isr().read().rrif().bit_is_set() {
         ltdc.ier().modify(|_, w| w.rrie().clear_bit());
         ltdc.icr().write(|w| w.crrif().bit(true));
@@ -497,7 +489,7 @@ fn LCD_TFT() {
 /// LTDC global error interrupt
 #[interrupt]
 fn LCD_TFT_1() {
-    let ltdc = LTDC::take();
+    let ltdc = unsafe { &*pac::LTDC::ptr() };
     if ltdc.isr().read().terrif().bit_is_set() {
         ltdc.ier().modify(|_, w| w.terrie().clear_bit());
         ltdc.icr().write(|w| w.cterrif().bit(true));
@@ -516,3 +508,18 @@ fn LCD_TFT_1() {
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    InitError(&'static str),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InitError(e) => f.write_fmt(format_args!("InitError: {}", e)),
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
@@ -0,0 +1,251 @@
+use core::ops::Deref;
+
+use cortex_m::{ asm, peripheral::SCB };
+
+use crate::{
+    flash::FLASH,
+    pac,
+    rcc::{ self, wait_until, Clocks, PeripheralClock, RCC },
+    time::Hertz,
+    Peripheral,
+};
+
+/// Owned handle of the power controller, obtained from [`Peripherals`](crate::Peripherals).
+pub struct PWR(pub(crate) ());
+
+impl Deref for PWR {
+    type Target = pac::pwr::RegisterBlock;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*pac::PWR::ptr() }
+    }
+}
+
+impl Peripheral for PWR {
+    const CLOCK: PeripheralClock = PeripheralClock::PWR;
+}
+
+impl PWR {
+    #[inline]
+    pub fn voltage_scale(&self) -> VoltageScale {
+        VoltageScale::from_bits(self.cr().read().vos().bits() as _)
+    }
+
+    /// Selects the main regulator output voltage.
+    ///
+    /// The new scale only takes effect once the main PLL is enabled, so this must be
+    /// called while the PLL is stopped.
+    #[inline]
+    pub fn set_voltage_scale(&mut self, scale: VoltageScale) {
+        unsafe {
+            self.cr().modify(|_, w| w.vos().bits(scale as _));
+        }
+    }
+
+    /// Waits until the regulator reached the voltage selected by
+    /// [`set_voltage_scale`](Self::set_voltage_scale). Only meaningful with the PLL enabled.
+    #[inline]
+    pub fn wait_voltage_scale_ready(&self) -> rcc::Result<()> {
+        wait_until(|| self.csr().read().vosrdy().bit_is_set(), rcc::Error::VoltageScaleTimeout)
+    }
+
+    /// Enables over-drive mode and switches the regulator to it.
+    ///
+    /// Must be called with HSI or HSE selected as system clock and the main PLL enabled.
+    pub fn enable_overdrive(&mut self) -> rcc::Result<()> {
+        self.cr().modify(|_, w| w.oden().set_bit());
+        wait_until(|| self.csr().read().odrdy().bit_is_set(), rcc::Error::OverdriveTimeout)?;
+
+        self.cr().modify(|_, w| w.odswen().set_bit());
+        wait_until(|| self.csr().read().odswrdy().bit_is_set(), rcc::Error::OverdriveTimeout)
+    }
+
+    /// Leaves over-drive mode. Must be called with HSI or HSE selected as system clock.
+    pub fn disable_overdrive(&mut self) -> rcc::Result<()> {
+        self.cr().modify(|_, w| {
+            w.oden().clear_bit();
+            w.odswen().clear_bit()
+        });
+        wait_until(|| self.csr().read().odswrdy().bit_is_clear(), rcc::Error::OverdriveTimeout)
+    }
+
+    #[inline]
+    pub fn is_overdrive_enabled(&self) -> bool {
+        self.csr().read().odswrdy().bit_is_set()
+    }
+
+    /// Allows writes to the backup domain (RTC, backup registers and RCC_BDCR).
+    #[inline]
+    pub fn enable_backup_domain_write_access(&mut self) -> rcc::Result<()> {
+        self.cr().modify(|_, w| w.dbp().set_bit());
+        wait_until(|| self.cr().read().dbp().bit_is_set(), rcc::Error::BackupDomainTimeout)
+    }
+
+    #[inline]
+    pub fn disable_backup_domain_write_access(&mut self) {
+        self.cr().modify(|_, w| w.dbp().clear_bit());
+    }
+
+    /// Stops the core clock until an interrupt or event, peripherals keep running.
+    pub fn enter_sleep(&mut self, entry: SleepEntry) {
+        scb().clear_sleepdeep();
+        wait_for(entry);
+    }
+
+    /// Returns to Sleep mode (or Stop/Standby, whichever was entered last) when the
+    /// last pending interrupt handler returns, instead of resuming thread mode.
+    #[inline]
+    pub fn set_sleep_on_exit(&mut self, enabled: bool) {
+        if enabled {
+            scb().set_sleeponexit();
+        } else {
+            scb().clear_sleeponexit();
+        }
+    }
+
+    /// Stops all clocks of the 1.2 V domain until an EXTI interrupt or event.
+    ///
+    /// The device wakes up on HSI, the clock tree set with
+    /// [`RCC::configure_system_clock`] is restored before returning.
+    pub fn enter_stop(
+        &mut self,
+        rcc: &mut RCC,
+        flash: &mut FLASH,
+        config: StopConfig
+    ) -> rcc::Result<Clocks> {
+        self.cr().modify(|_, w| {
+            w.pdds().clear_bit();
+            w.lpds().bit(config.regulator == StopRegulator::LowPower);
+            w.fpds().bit(config.flash_power_down);
+            w.cwuf().set_bit()
+        });
+
+        let mut scb = scb();
+        scb.set_sleepdeep();
+        wait_for(config.entry);
+        scb.clear_sleepdeep();
+
+        rcc.restore_system_clock(flash, self)
+    }
+
+    /// Enters Standby mode. The device leaves it through a reset, on a rising edge of
+    /// WKUP (PA0) if `wakeup_pin` is set, an RTC event, IWDG or NRST.
+    pub fn enter_standby(&mut self, wakeup_pin: bool) -> ! {
+        self.csr().modify(|_, w| w.ewup().bit(wakeup_pin));
+        self.cr().modify(|_, w| {
+            w.pdds().set_bit();
+            w.cwuf().set_bit();
+            w.csbf().set_bit()
+        });
+
+        scb().set_sleepdeep();
+        loop {
+            asm::dsb();
+            asm::wfi();
+        }
+    }
+
+    /// Whether the device was reset out of Standby mode.
+    #[inline]
+    pub fn was_in_standby(&self) -> bool {
+        self.csr().read().sbf().bit_is_set()
+    }
+
+    #[inline]
+    pub fn clear_standby_flag(&mut self) {
+        self.cr().modify(|_, w| w.csbf().set_bit());
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepEntry {
+    /// Wait for interrupt
+    WFI,
+    /// Wait for event
+    WFE,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopRegulator {
+    Main,
+    LowPower,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StopConfig {
+    pub regulator: StopRegulator,
+    /// Powers the flash down, lower consumption for a longer wake-up time
+    pub flash_power_down: bool,
+    pub entry: SleepEntry,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VoltageScale {
+    Scale3 = 0b01,
+    Scale2 = 0b10,
+    Scale1 = 0b11,
+}
+
+impl VoltageScale {
+    pub fn from_bits(val: u32) -> Self {
+        match val {
+            0b01 => Self::Scale3,
+            0b10 => Self::Scale2,
+            0b11 => Self::Scale1,
+            _ => panic!(),
+        }
+    }
+
+    /// Maximum HCLK frequency allowed in this scale.
+    pub const fn max_hclk(&self, overdrive: bool) -> Hertz {
+        match (self, overdrive) {
+            (VoltageScale::Scale3, _) => Hertz::MHz(120),
+            (VoltageScale::Scale2, false) => Hertz::MHz(144),
+            (VoltageScale::Scale2, true) => Hertz::MHz(168),
+            (VoltageScale::Scale1, false) => Hertz::MHz(168),
+            (VoltageScale::Scale1, true) => Hertz::MHz(180),
+        }
+    }
+
+    /// Lowest power regulator setting able to run at `hclk`, returned together with
+    /// whether over-drive is required.
+    pub const fn for_hclk(hclk: Hertz) -> (VoltageScale, bool) {
+        let hclk = hclk.raw();
+
+        if hclk <= VoltageScale::Scale3.max_hclk(false).raw() {
+            (VoltageScale::Scale3, false)
+        } else if hclk <= VoltageScale::Scale2.max_hclk(false).raw() {
+            (VoltageScale::Scale2, false)
+        } else if hclk <= VoltageScale::Scale1.max_hclk(false).raw() {
+            (VoltageScale::Scale1, false)
+        } else {
+            (VoltageScale::Scale1, true)
+        }
+    }
+}
+
+fn scb() -> SCB {
+    unsafe { cortex_m::Peripherals::steal().SCB }
+}
+
+fn wait_for(entry: SleepEntry) {
+    asm::dsb();
+    match entry {
+        SleepEntry::WFI => asm::wfi(),
+        SleepEntry::WFE => asm::wfe(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn voltage_scale_for_hclk() {
+        assert_eq!(VoltageScale::for_hclk(Hertz::MHz(16)), (VoltageScale::Scale3, false));
+        assert_eq!(VoltageScale::for_hclk(Hertz::MHz(120)), (VoltageScale::Scale3, false));
+        assert_eq!(VoltageScale::for_hclk(Hertz::MHz(144)), (VoltageScale::Scale2, false));
+        assert_eq!(VoltageScale::for_hclk(Hertz::MHz(168)), (VoltageScale::Scale1, false));
+        assert_eq!(VoltageScale::for_hclk(Hertz::MHz(180)), (VoltageScale::Scale1, true));
+    }
+}
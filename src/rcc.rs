@@ -1,97 +1,588 @@
-use core::{ cell::Cell, ops::Deref };
+use core::{ cell::Cell, cmp, fmt, ops::Deref, sync::atomic::{ AtomicBool, AtomicU32, Ordering } };
 
-use crate::{ pac, PeripheralRef };
+use cortex_m::interrupt::{ self, Mutex };
 
-const EXTERNAL_OSC_FREQ: core::cell::Cell<u32> = Cell::new(8_000_000u32);
+use crate::{
+    flash::{ VoltageRange, FLASH },
+    gpio::{ self, pin::Speed, typestate::{ mode::Alternate, PA, PC } },
+    pac,
+    pwr::{ VoltageScale, PWR },
+    time::Hertz,
+    Peripheral,
+};
 
-pub struct RCC(pac::rcc::RegisterBlock);
+const HSI_FREQ: Hertz = Hertz::MHz(16);
+const HSE_OSC_FREQ_MIN: u32 = 4_000_000u32;
+const HSE_OSC_FREQ_MAX: u32 = 26_000_000u32;
+const HSE_BYPASS_FREQ_MIN: u32 = 1_000_000u32;
+const HSE_BYPASS_FREQ_MAX: u32 = 50_000_000u32;
+const LSE_FREQ: Hertz = Hertz::Hz(32_768);
+const LSI_FREQ: Hertz = Hertz::kHz(32);
+
+/// HSE frequency of the last applied [`HSEConfig`], 0 if HSE was never configured.
+static HSE_FREQ: AtomicU32 = AtomicU32::new(0);
+
+/// Set by [`RCC::handle_css_interrupt`] once the hardware has fallen back to HSI.
+static CSS_FAILOVER: AtomicBool = AtomicBool::new(false);
+
+/// Number of polls of a ready flag before giving up. HSE start-up is the slowest event
+/// waited for, this covers well over its 100 ms worst case when running from HSI.
+const READY_TIMEOUT: u32 = 1_000_000;
+
+/// LSE crystals may need up to 2 s to start, far longer than any other oscillator.
+const LSE_READY_TIMEOUT: u32 = 100_000_000;
+
+/// Owned handle of the reset and clock controller, obtained from
+/// [`Peripherals`](crate::Peripherals). Drivers borrow it to gate their clock.
+pub struct RCC(pub(crate) ());
 
 impl Deref for RCC {
     type Target = pac::rcc::RegisterBlock;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        unsafe { &*pac::RCC::ptr() }
     }
 }
 
-impl PeripheralRef for RCC {
-    type Output = RCC;
+impl RCC {
+    /// Switches SYSCLK to the requested source, reprogramming the PLL if it is already
+    /// running, and applies the bus prescalers, flash latency and regulator settings in an
+    /// order that keeps every intermediate state within the device limits.
+    ///
+    /// Leaving the PLL stops it and lowers the regulator voltage scale. PLLM and the PLL
+    /// source are shared with PLLSAI and PLLI2S, so changing them while either is running
+    /// is refused with [`Error::PllInputInUse`].
+    ///
+    /// An error can leave the change half applied: the flash latency is only lowered
+    /// after success, so the device stays within its limits, but the bus prescalers or
+    /// SYSCLK source may already differ from the previous setup. Read the state back with
+    /// [`RCC::clocks`] before carrying on.
+    pub fn configure_system_clock(
+        &mut self,
+        flash: &mut FLASH,
+        pwr: &mut PWR,
+        syscfg: SystemClockConfig,
+        buscfg: BUSConfig,
+        vdd: VoltageRange
+    ) -> Result<Clocks> {
+        match &syscfg {
+            SystemClockConfig::HSE(hse) => self.check_hse(hse)?,
+            SystemClockConfig::PLL(PLLConfig { clock_source: PLLSource::HSE(hse), .. }) => {
+                self.check_hse(hse)?
+            }
+            _ => (),
+        }
+
+        if let SystemClockConfig::PLL(config) = &syscfg {
+            let pllcfgr = self.pllcfgr().read();
+            let cr = self.cr().read();
+            let input_changes =
+                pllcfgr.pllm().bits() != config.pllm ||
+                pllcfgr.pllsrc().bit() != matches!(config.clock_source, PLLSource::HSE(_));
+
+            if input_changes && (cr.pllsaion().bit_is_set() || cr.plli2son().bit_is_set()) {
+                return Err(Error::PllInputInUse);
+            }
+        }
+
+        pwr.enable_clock(self);
+
+        let sysclk = match &syscfg {
+            SystemClockConfig::HSI(_) => HSI_FREQ,
+            SystemClockConfig::HSE(config) => config.freq,
+            SystemClockConfig::PLL(config) => config.sysclk_freq(),
+        };
+        let current_sysclk = self.sysclk_freq();
+        let ahb_div = buscfg.ahb_prescaler.division_factor();
+        let current_ahb_div = self.ahb_prescaler().division_factor();
+        let (voltage_scale, overdrive) = VoltageScale::for_hclk(sysclk / ahb_div);
+
+        // As in RM0090, dividers that grow are applied before SYSCLK is switched and those
+        // that shrink only after it, so no bus runs above its limit in between. HCLK then
+        // stays below the larger of its current and target value, which the flash latency
+        // is raised to cover before anything else happens.
+        let latency = vdd.wait_states(sysclk / ahb_div).ok_or(Error::UnsupportedFrequency)?;
+        let current_latency = vdd.wait_states(current_sysclk / current_ahb_div).unwrap_or(15);
+
+        let raised_latency = latency.max(current_latency);
+        if raised_latency > flash.latency() {
+            flash.set_latency(raised_latency)?;
+        }
+
+        let interim = self.widest_bus_prescalers(&buscfg);
+        self.set_bus_prescalers(&interim);
+
+        match syscfg {
+            SystemClockConfig::HSI(t) => {
+                if !self.cfgr().read().sws().is_hsi() {
+                    self.enable_hsi(Some(t))?;
+                    self.switch_system_clock(SystemClockSource::HSI)?;
+                }
+
+                self.leave_pll(pwr, voltage_scale)?;
+            }
+            SystemClockConfig::HSE(config) => {
+                if !self.cfgr().read().sws().is_hse() {
+                    self.enable_hse(config)?;
+                    self.switch_system_clock(SystemClockSource::HSE)?;
+                }
+
+                self.leave_pll(pwr, voltage_scale)?;
+            }
+            SystemClockConfig::PLL(config) => {
+                // PLLCFGR and VOS can only be written while the PLL is stopped, so run
+                // from HSI while it is being reprogrammed.
+                if self.cfgr().read().sws().is_pll() {
+                    self.enable_hsi(None)?;
+                    self.switch_system_clock(SystemClockSource::HSI)?;
+                }
+
+                self.disable_pll()?;
+
+                match config.clock_source {
+                    PLLSource::HSI => self.enable_hsi(None)?,
+                    PLLSource::HSE(hse) => self.enable_hse(hse)?,
+                }
+
+                unsafe {
+                    self.pllcfgr().write(|w| {
+                        w.pllsrc().bit(matches!(config.clock_source, PLLSource::HSE(_)));
+                        w.pllm().bits(config.pllm);
+                        w.plln().bits(config.plln);
+                        w.pllp().bits(config.system_clock_div_factor as _);
+                        w.pllq().bits(config.pllq)
+                    });
+                }
+
+                // VOS is only writable with the PLL off and takes effect once it runs
+                pwr.set_voltage_scale(voltage_scale);
+                self.cr().modify(|_, w| w.pllon().set_bit());
+
+                if overdrive {
+                    pwr.enable_overdrive()?;
+                } else if pwr.is_overdrive_enabled() {
+                    pwr.disable_overdrive()?;
+                }
+
+                wait_until(|| self.cr().read().pllrdy().bit_is_set(), Error::PllLockTimeout)?;
+                pwr.wait_voltage_scale_ready()?;
+
+                self.switch_system_clock(SystemClockSource::PLL)?;
+            }
+        }
+
+        self.set_bus_prescalers(&buscfg);
 
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::RCC::PTR as *mut Self::Output).as_mut().unwrap() }
+        if latency < flash.latency() {
+            flash.set_latency(latency)?;
+        }
+
+        interrupt::free(|cs| SYSTEM_CLOCK_CONFIG.borrow(cs).set(Some((syscfg, buscfg, vdd))));
+        CSS_FAILOVER.store(false, Ordering::Relaxed);
+
+        Ok(self.clocks())
     }
-}
 
-impl RCC {
-    pub fn configure_system_clock(&mut self, syscfg: SystemClockConfig, buscfg: BUSConfig) {
-        unsafe {
-            self.apb1enr().modify(|_, w| w.pwren().set_bit());
+    /// Re-applies the configuration last passed to [`RCC::configure_system_clock`], e.g.
+    /// after leaving Stop mode, which always restarts on HSI with the PLLs and HSE off.
+    ///
+    /// After an HSE failure reported by [`RCC::handle_css_interrupt`], HSI is used instead
+    /// of the recorded clock source.
+    pub fn restore_system_clock(&mut self, flash: &mut FLASH, pwr: &mut PWR) -> Result<Clocks> {
+        match interrupt::free(|cs| SYSTEM_CLOCK_CONFIG.borrow(cs).get()) {
+            Some((_, buscfg, vdd)) if CSS_FAILOVER.load(Ordering::Relaxed) => {
+                let trim = self.cr().read().hsitrim().bits();
+                self.configure_system_clock(flash, pwr, SystemClockConfig::HSI(trim), buscfg, vdd)
+            }
+            Some((syscfg, buscfg, vdd)) => {
+                self.configure_system_clock(flash, pwr, syscfg, buscfg, vdd)
+            }
+            None => Ok(self.clocks()),
+        }
+    }
+
+    /// Drops over-drive, stops the PLL and selects `voltage_scale` once SYSCLK runs from
+    /// HSI or HSE.
+    fn leave_pll(&mut self, pwr: &mut PWR, voltage_scale: VoltageScale) -> Result<()> {
+        if pwr.is_overdrive_enabled() {
+            pwr.disable_overdrive()?;
+        }
+
+        // VOS is only writable with the PLL off
+        self.disable_pll()?;
+        pwr.set_voltage_scale(voltage_scale);
+
+        Ok(())
+    }
+
+    fn disable_pll(&mut self) -> Result<()> {
+        if self.cr().read().pllon().bit_is_set() {
+            self.cr().modify(|_, w| w.pllon().clear_bit());
+            wait_until(|| self.cr().read().pllrdy().bit_is_clear(), Error::PllLockTimeout)?;
+        }
+
+        Ok(())
+    }
+
+    /// Per bus, the larger of the current divider and the one in `buscfg`.
+    fn widest_bus_prescalers(&self, buscfg: &BUSConfig) -> BUSConfig {
+        BUSConfig {
+            ahb_prescaler: cmp::max_by_key(
+                self.ahb_prescaler(),
+                buscfg.ahb_prescaler,
+                AHBPrescaler::division_factor
+            ),
+            apb1_prescaler: cmp::max_by_key(
+                self.apb1_prescaler(),
+                buscfg.apb1_prescaler,
+                APBPrescaler::division_factor
+            ),
+            apb2_prescaler: cmp::max_by_key(
+                self.apb2_prescaler(),
+                buscfg.apb2_prescaler,
+                APBPrescaler::division_factor
+            ),
+        }
+    }
 
+    fn set_bus_prescalers(&mut self, buscfg: &BUSConfig) {
+        unsafe {
             self.cfgr().modify(|_, w| {
                 w.hpre().bits(buscfg.ahb_prescaler as _);
                 w.ppre1().bits(buscfg.apb1_prescaler as _);
                 w.ppre2().bits(buscfg.apb2_prescaler as _)
             });
+        }
+    }
 
-            let flash = pac::FLASH::PTR.as_ref().unwrap();
-            flash.acr().modify(|_, w| w.latency().bits(5));
-
-            match syscfg {
-                SystemClockConfig::HSI(t) => {
-                    if !self.cfgr().read().sws().is_hsi() {
-                        self.cr().modify(|_, w| {
-                            w.hsitrim().bits(t);
-                            w.hsion().set_bit()
-                        });
-                        while self.cr().read().hsirdy().bit_is_clear() {}
+    fn switch_system_clock(&mut self, source: SystemClockSource) -> Result<()> {
+        unsafe {
+            self.cfgr().modify(|_, w| w.sw().bits(source as _));
+        }
+        wait_until(|| self.sysclock_clock_source() == source, Error::SwitchTimeout)
+    }
 
-                        self.cfgr().modify(|_, w| w.sw().hsi());
-                        while !self.cfgr().read().sws().is_hsi() {}
-                    }
+    fn enable_hsi(&mut self, trim: Option<u8>) -> Result<()> {
+        unsafe {
+            self.cr().modify(|_, w| {
+                if let Some(t) = trim {
+                    w.hsitrim().bits(t);
                 }
-                SystemClockConfig::HSE(_) => {
-                    if !self.cfgr().read().sws().is_hse() {
-                        self.cr().modify(|_, w| w.hseon().set_bit());
-                        while self.cr().read().hserdy().bit_is_clear() {}
+                w.hsion().set_bit()
+            });
+        }
+        wait_until(|| self.cr().read().hsirdy().bit_is_set(), Error::HsiNotReady)
+    }
 
-                        self.cfgr().modify(|_, w| w.sw().hse());
-                        while !self.cfgr().read().sws().is_hse() {}
-                    }
-                }
-                SystemClockConfig::PLL(config) => {
-                    if !self.cfgr().read().sws().is_pll() {
-                        match config.clock_source {
-                            PLLClockSource::HSI => {
-                                self.cr().modify(|_, w| w.hsion().set_bit());
-                                while self.cr().read().hsirdy().bit_is_clear() {}
-                            }
-                            PLLClockSource::HSE => {
-                                self.cr().modify(|_, w| w.hseon().set_bit());
-                                while self.cr().read().hserdy().bit_is_clear() {}
-                            }
-                        }
-                        self.pllcfgr().write(|w| {
-                            w.pllsrc().bit(config.clock_source == PLLClockSource::HSE);
-                            w.pllm().bits(config.pllm);
-                            w.plln().bits(config.plln);
-                            w.pllp().bits(config.system_clock_div_factor as _);
-                            w.pllq().bits(config.pllq)
-                        });
-                        self.cr().modify(|_, w| w.pllon().set_bit());
-                        while self.cr().read().pllrdy().bit_is_clear() {}
-
-                        self.cfgr().modify(|_, w| w.sw().pll());
-                        while !self.cfgr().read().sws().is_pll() {}
-                    }
-                }
-            }
+    /// Checks `config` against its frequency range and the mode HSE may already run in.
+    fn check_hse(&self, config: &HSEConfig) -> Result<()> {
+        if !config.is_valid() {
+            return Err(Error::HseFrequencyOutOfRange);
+        }
+
+        // HSEBYP can only be written while the oscillator is stopped
+        let cr = self.cr().read();
+        if cr.hseon().bit_is_set() && cr.hsebyp().bit() != (config.mode == HSEMode::Bypass) {
+            return Err(Error::HseModeMismatch);
+        }
+
+        Ok(())
+    }
+
+    fn enable_hse(&mut self, config: HSEConfig) -> Result<()> {
+        self.check_hse(&config)?;
+
+        if self.cr().read().hseon().bit_is_clear() {
+            self.cr().modify(|_, w| w.hsebyp().bit(config.mode == HSEMode::Bypass));
+            self.cr().modify(|_, w| w.hseon().set_bit());
+        }
+        wait_until(|| self.cr().read().hserdy().bit_is_set(), Error::HseNotReady)?;
+
+        HSE_FREQ.store(config.freq.raw(), Ordering::Relaxed);
+
+        if config.css {
+            self.enable_css();
+        }
+
+        Ok(())
+    }
+
+    /// Enables the Clock Security System.
+    ///
+    /// On HSE failure the hardware switches SYSCLK to HSI, stops HSE and the PLL (when fed
+    /// from HSE) and raises an NMI. The application's `NonMaskableInt` handler must then call
+    /// [`RCC::handle_css_interrupt`].
+    #[inline]
+    pub fn enable_css(&mut self) {
+        self.cr().modify(|_, w| w.csson().set_bit());
+    }
+
+    #[inline]
+    pub fn disable_css(&mut self) {
+        self.cr().modify(|_, w| w.csson().clear_bit());
+    }
+
+    /// Acknowledges a Clock Security System event, to be called from the application's
+    /// `NonMaskableInt` handler. Returns the clocks now running from HSI, or `None` if the
+    /// NMI was not raised by the CSS.
+    ///
+    /// The recorded HSE frequency is cleared and [`RCC::restore_system_clock`] falls back
+    /// to HSI, so frozen [`Clocks`] taken before the failure must be replaced by the
+    /// returned value. The handler needs the `RCC` handle, e.g. shared with it through a
+    /// `Mutex`.
+    pub fn handle_css_interrupt(&mut self) -> Option<Clocks> {
+        if self.cir().read().cssf().bit_is_clear() {
+            return None;
+        }
+
+        self.cir().modify(|_, w| w.cssc().set_bit());
+        HSE_FREQ.store(0, Ordering::Relaxed);
+        CSS_FAILOVER.store(true, Ordering::Relaxed);
+
+        Some(self.clocks())
+    }
+
+    pub fn enable_peripheral_clock(&mut self, clock: PeripheralClock) {
+        self.modify_gate(Gate::Enable, clock, true);
+
+        // read back so the clock is running before the peripheral is first accessed
+        self.is_peripheral_clock_enabled(clock);
+    }
+
+    #[inline]
+    pub fn disable_peripheral_clock(&mut self, clock: PeripheralClock) {
+        self.modify_gate(Gate::Enable, clock, false);
+    }
+
+    #[inline]
+    pub fn is_peripheral_clock_enabled(&self, clock: PeripheralClock) -> bool {
+        let enr = match clock.bus {
+            Bus::AHB1 => self.ahb1enr().read().bits(),
+            Bus::AHB2 => self.ahb2enr().read().bits(),
+            Bus::AHB3 => self.ahb3enr().read().bits(),
+            Bus::APB1 => self.apb1enr().read().bits(),
+            Bus::APB2 => self.apb2enr().read().bits(),
+        };
+
+        enr & (1 << clock.bit) != 0
+    }
+
+    #[inline]
+    pub fn enable_peripheral_clock_in_sleep(&mut self, clock: PeripheralClock) {
+        self.modify_gate(Gate::EnableInSleep, clock, true);
+    }
+
+    #[inline]
+    pub fn disable_peripheral_clock_in_sleep(&mut self, clock: PeripheralClock) {
+        self.modify_gate(Gate::EnableInSleep, clock, false);
+    }
+
+    #[inline]
+    pub fn reset_peripheral(&mut self, clock: PeripheralClock) {
+        self.modify_gate(Gate::Reset, clock, true);
+        self.modify_gate(Gate::Reset, clock, false);
+    }
+
+    /// Sets or clears the bit of `clock` in its RSTR, ENR or LPENR register. Done in a
+    /// critical section, as `ExtiFuture` also gates SYSCFG from an executor.
+    fn modify_gate(&mut self, gate: Gate, clock: PeripheralClock, set: bool) {
+        let mask = 1 << clock.bit;
+
+        macro_rules! rmw {
+            ($reg:ident) => {
+                self.$reg().modify(|r, w| unsafe {
+                    w.bits(if set { r.bits() | mask } else { r.bits() & !mask })
+                })
+            };
         }
+
+        interrupt::free(|_| {
+            match (gate, clock.bus) {
+                (Gate::Reset, Bus::AHB1) => rmw!(ahb1rstr),
+                (Gate::Reset, Bus::AHB2) => rmw!(ahb2rstr),
+                (Gate::Reset, Bus::AHB3) => rmw!(ahb3rstr),
+                (Gate::Reset, Bus::APB1) => rmw!(apb1rstr),
+                (Gate::Reset, Bus::APB2) => rmw!(apb2rstr),
+                (Gate::Enable, Bus::AHB1) => rmw!(ahb1enr),
+                (Gate::Enable, Bus::AHB2) => rmw!(ahb2enr),
+                (Gate::Enable, Bus::AHB3) => rmw!(ahb3enr),
+                (Gate::Enable, Bus::APB1) => rmw!(apb1enr),
+                (Gate::Enable, Bus::APB2) => rmw!(apb2enr),
+                (Gate::EnableInSleep, Bus::AHB1) => rmw!(ahb1lpenr),
+                (Gate::EnableInSleep, Bus::AHB2) => rmw!(ahb2lpenr),
+                (Gate::EnableInSleep, Bus::AHB3) => rmw!(ahb3lpenr),
+                (Gate::EnableInSleep, Bus::APB1) => rmw!(apb1lpenr),
+                (Gate::EnableInSleep, Bus::APB2) => rmw!(apb2lpenr),
+            };
+        });
+    }
+
+    pub fn enable_lsi(&mut self) -> Result<()> {
+        self.csr().modify(|_, w| w.lsion().set_bit());
+        wait_until(|| self.csr().read().lsirdy().bit_is_set(), Error::LsiNotReady)
+    }
+
+    #[inline]
+    pub fn disable_lsi(&mut self) {
+        self.csr().modify(|_, w| w.lsion().clear_bit());
+    }
+
+    /// Causes of the last reset. The flags accumulate over resets until cleared with
+    /// [`RCC::clear_reset_reason`].
+    #[inline]
+    pub fn reset_reason(&self) -> ResetReason {
+        ResetReason::from_bits(self.csr().read().bits())
+    }
+
+    #[inline]
+    pub fn clear_reset_reason(&mut self) {
+        self.csr().modify(|_, w| w.rmvf().set_bit());
+    }
+
+    /// Starts the 32.768 kHz low-speed external oscillator.
+    ///
+    /// The STM32F429 LSE has a fixed drive level, only the bypass mode is selectable.
+    pub fn enable_lse(&mut self, pwr: &mut PWR, mode: LSEMode) -> Result<()> {
+        self.unlock_backup_domain(pwr)?;
+
+        // LSEBYP can only be written while the oscillator is stopped
+        if self.bdcr().read().lseon().bit_is_clear() {
+            self.bdcr().modify(|_, w| w.lsebyp().bit(mode == LSEMode::Bypass));
+            self.bdcr().modify(|_, w| w.lseon().set_bit());
+        }
+
+        wait_until_timeout(
+            LSE_READY_TIMEOUT,
+            || self.bdcr().read().lserdy().bit_is_set(),
+            Error::LseNotReady
+        )
+    }
+
+    pub fn disable_lse(&mut self, pwr: &mut PWR) -> Result<()> {
+        self.unlock_backup_domain(pwr)?;
+        self.bdcr().modify(|_, w| w.lseon().clear_bit());
+        // LSEBYP can only be cleared once the oscillator has stopped
+        wait_until(|| self.bdcr().read().lserdy().bit_is_clear(), Error::LseStopTimeout)?;
+        self.bdcr().modify(|_, w| w.lsebyp().clear_bit());
+
+        Ok(())
+    }
+
+    /// Selects the RTC clock and enables it.
+    ///
+    /// The selection can not be changed once made, except by a backup domain reset,
+    /// see [`RCC::reset_backup_domain`]. Asking for another source than the one already
+    /// selected fails with [`Error::RtcClockSourceLocked`]. With [`RTCClockSource::HSE`]
+    /// the prescaler set by [`RCC::set_rtc_hse_prescaler`] must bring HSE down to 1 MHz.
+    pub fn configure_rtc_clock(&mut self, pwr: &mut PWR, source: RTCClockSource) -> Result<()> {
+        let current = self.rtc_clock_source();
+        if current != RTCClockSource::NoClock && current != source {
+            return Err(Error::RtcClockSourceLocked);
+        }
+
+        self.unlock_backup_domain(pwr)?;
+        unsafe {
+            self.bdcr().modify(|_, w| {
+                w.rtcsel().bits(source as _);
+                w.rtcen().bit(source != RTCClockSource::NoClock)
+            });
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn rtc_clock_source(&self) -> RTCClockSource {
+        RTCClockSource::from_bits(self.bdcr().read().rtcsel().bits() as _)
+    }
+
+    /// Division factor of HSE for the RTC clock, 2 to 31.
+    #[inline]
+    pub fn set_rtc_hse_prescaler(&mut self, prescaler: u8) {
+        assert!((2..=31).contains(&prescaler));
+
+        unsafe {
+            self.cfgr().modify(|_, w| w.rtcpre().bits(prescaler));
+        }
+    }
+
+    /// Resets the whole backup domain: RTC, backup registers, LSE and the RTC clock
+    /// selection.
+    pub fn reset_backup_domain(&mut self, pwr: &mut PWR) -> Result<()> {
+        self.unlock_backup_domain(pwr)?;
+        self.bdcr().modify(|_, w| w.bdrst().set_bit());
+        self.bdcr().modify(|_, w| w.bdrst().clear_bit());
+
+        Ok(())
+    }
+
+    /// Outputs `source` divided by `prescaler` on MCO1, which is PA8 in alternate function 0.
+    ///
+    /// Fails without touching the clock output if PA8 is locked below very high speed.
+    pub fn configure_mco1(
+        &mut self,
+        mut pin: PA<8, Alternate<0>>,
+        source: MCO1Source,
+        prescaler: MCOPrescaler
+    ) -> gpio::Result<()> {
+        pin.set_speed(Speed::VeryHigh)?;
+
+        unsafe {
+            self.cfgr().modify(|_, w| {
+                w.mco1().bits(source as _);
+                w.mco1pre().bits(prescaler as _)
+            });
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn mco1_source(&self) -> MCO1Source {
+        MCO1Source::from_bits(self.cfgr().read().mco1().bits() as _)
+    }
+
+    /// Outputs `source` divided by `prescaler` on MCO2, which is PC9 in alternate function 0.
+    ///
+    /// Fails without touching the clock output if PC9 is locked below very high speed.
+    pub fn configure_mco2(
+        &mut self,
+        mut pin: PC<9, Alternate<0>>,
+        source: MCO2Source,
+        prescaler: MCOPrescaler
+    ) -> gpio::Result<()> {
+        pin.set_speed(Speed::VeryHigh)?;
+
+        unsafe {
+            self.cfgr().modify(|_, w| {
+                w.mco2().bits(source as _);
+                w.mco2pre().bits(prescaler as _)
+            });
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn mco2_source(&self) -> MCO2Source {
+        MCO2Source::from_bits(self.cfgr().read().mco2().bits() as _)
+    }
+
+    fn unlock_backup_domain(&mut self, pwr: &mut PWR) -> Result<()> {
+        pwr.enable_clock(self);
+        pwr.enable_backup_domain_write_access()
     }
 
-    pub fn configure_pllsai(&mut self, config: PLLSAIConfig) {
+    /// Fails with [`Error::InvalidPllSaiConfig`] before touching PLLSAI if `config` is
+    /// out of range for the current PLL input, see [`PLLSAIConfig::is_valid`].
+    pub fn configure_pllsai(&mut self, clocks: &Clocks, config: PLLSAIConfig) -> Result<Clocks> {
+        if !config.is_valid(self.pll_vco_input_freq()) {
+            return Err(Error::InvalidPllSaiConfig);
+        }
+
         unsafe {
             self.cr().modify(|_, w| w.pllsaion().clear_bit());
-            while self.cr().read().pllsairdy().bit_is_set() {}
+            wait_until(|| self.cr().read().pllsairdy().bit_is_clear(), Error::PllSaiLockTimeout)?;
 
             self.pllsaicfgr().write(|w| {
                 w.pllsain().bits(config.pllsain as _);
@@ -101,10 +592,105 @@ impl RCC {
             self.dckcfgr().modify(|_, w| w.pllsaidivr().bits(config.lcd_div_factor as _));
 
             self.cr().modify(|_, w| w.pllsaion().set_bit());
-            while self.cr().read().pllsairdy().bit_is_clear() {}
+            wait_until(|| self.cr().read().pllsairdy().bit_is_set(), Error::PllSaiLockTimeout)?;
+        }
+
+        Ok(Clocks {
+            lcdclk: self.lcdclk_freq(),
+            ..*clocks
+        })
+    }
+
+    pub fn configure_plli2s(&mut self, config: PLLI2SConfig) -> Result<()> {
+        if !config.is_valid() {
+            return Err(Error::InvalidPllI2SConfig);
+        }
+
+        unsafe {
+            self.cr().modify(|_, w| w.plli2son().clear_bit());
+            wait_until(|| self.cr().read().plli2srdy().bit_is_clear(), Error::PllI2SLockTimeout)?;
+
+            self.plli2scfgr().write(|w| {
+                w.plli2sn().bits(config.plli2sn);
+                w.plli2sq().bits(config.plli2sq);
+                w.plli2sr().bits(config.plli2sr)
+            });
+            self.dckcfgr().modify(|_, w| w.plli2sdivq().bits(config.sai_div_factor - 1));
+
+            self.cr().modify(|_, w| w.plli2son().set_bit());
+            wait_until(|| self.cr().read().plli2srdy().bit_is_set(), Error::PllI2SLockTimeout)?;
+        }
+
+        Ok(())
+    }
+
+    #[inline]
+    pub fn set_i2s_clock_source(&mut self, source: I2SClockSource) {
+        self.cfgr().modify(|_, w| w.i2ssrc().bit(source == I2SClockSource::External));
+    }
+
+    #[inline]
+    pub fn i2s_clock_source(&self) -> I2SClockSource {
+        I2SClockSource::from_bits(self.cfgr().read().i2ssrc().bit() as _)
+    }
+
+    #[inline]
+    pub fn set_sai_clock_source(&mut self, block: SAIBlock, source: SAIClockSource) {
+        unsafe {
+            self.dckcfgr().modify(|_, w| {
+                match block {
+                    SAIBlock::A => w.sai1asrc().bits(source as _),
+                    SAIBlock::B => w.sai1bsrc().bits(source as _),
+                }
+            });
+        }
+    }
+
+    #[inline]
+    pub fn sai_clock_source(&self, block: SAIBlock) -> SAIClockSource {
+        let dckcfgr = self.dckcfgr().read();
+        match block {
+            SAIBlock::A => SAIClockSource::from_bits(dckcfgr.sai1asrc().bits() as _),
+            SAIBlock::B => SAIClockSource::from_bits(dckcfgr.sai1bsrc().bits() as _),
+        }
+    }
+
+    /// Takes a snapshot of the clock tree as currently programmed in the RCC registers.
+    pub fn clocks(&self) -> Clocks {
+        let pclk1 = self.pclk1_freq();
+        let pclk2 = self.pclk2_freq();
+
+        let pll48clk = if self.cr().read().pllrdy().bit_is_set() {
+            Some(self.pll_vco_freq() / (self.pllcfgr().read().pllq().bits() as u32))
+        } else {
+            None
+        };
+
+        Clocks {
+            sysclk: self.sysclk_freq(),
+            hclk: self.hclk_freq(),
+            pclk1,
+            pclk2,
+            timclk1: self.apb1_timer_freq(),
+            timclk2: self.apb2_timer_freq(),
+            pll48clk,
+            lcdclk: self.lcdclk_freq(),
         }
     }
 
+    /// Selects how the timer kernel clocks are derived from the APB clocks.
+    ///
+    /// Frozen [`Clocks`] are not updated, take a new snapshot with [`RCC::clocks`].
+    #[inline]
+    pub fn set_timer_clock_multiplier(&mut self, multiplier: TimerClockMultiplier) {
+        self.dckcfgr().modify(|_, w| w.timpre().bit(multiplier == TimerClockMultiplier::X4));
+    }
+
+    #[inline]
+    pub fn timer_clock_multiplier(&self) -> TimerClockMultiplier {
+        TimerClockMultiplier::from_bits(self.dckcfgr().read().timpre().bit() as _)
+    }
+
     #[inline]
     pub fn sysclock_clock_source(&self) -> SystemClockSource {
         SystemClockSource::from_bits(self.cfgr().read().sws().bits() as _)
@@ -140,101 +726,851 @@ impl RCC {
         APBPrescaler::from_bits(self.cfgr().read().ppre1().bits() as _)
     }
 
-    #[inline]
-    pub fn apb2_prescaler(&self) -> APBPrescaler {
-        APBPrescaler::from_bits(self.cfgr().read().ppre2().bits() as _)
-    }
+    #[inline]
+    pub fn apb2_prescaler(&self) -> APBPrescaler {
+        APBPrescaler::from_bits(self.cfgr().read().ppre2().bits() as _)
+    }
+
+    #[inline]
+    pub fn sysclk_freq(&self) -> Hertz {
+        match self.sysclock_clock_source() {
+            SystemClockSource::HSI => HSI_FREQ,
+            SystemClockSource::HSE => hse_freq(),
+            SystemClockSource::PLL => {
+                let pllp = match self.pll_sysclock_division_factor() {
+                    PLLSysClockDivisionFactor::DividedBy2 => 2,
+                    PLLSysClockDivisionFactor::DividedBy4 => 4,
+                    PLLSysClockDivisionFactor::DividedBy6 => 6,
+                    PLLSysClockDivisionFactor::DividedBy8 => 8,
+                };
+
+                self.pll_vco_freq() / pllp
+            }
+        }
+    }
+
+    #[inline]
+    pub fn hclk_freq(&self) -> Hertz {
+        self.sysclk_freq() / self.ahb_prescaler().division_factor()
+    }
+
+    #[inline]
+    pub fn pclk1_freq(&self) -> Hertz {
+        let apb1_div = match self.apb1_prescaler() {
+            APBPrescaler::NotDivided => 1,
+            APBPrescaler::DividedBy2 => 2,
+            APBPrescaler::DividedBy4 => 4,
+            APBPrescaler::DividedBy8 => 8,
+            APBPrescaler::DividedBy16 => 16,
+        };
+
+        self.hclk_freq() / apb1_div
+    }
+
+    #[inline]
+    pub fn pclk2_freq(&self) -> Hertz {
+        let apb2_div = match self.apb2_prescaler() {
+            APBPrescaler::NotDivided => 1,
+            APBPrescaler::DividedBy2 => 2,
+            APBPrescaler::DividedBy4 => 4,
+            APBPrescaler::DividedBy8 => 8,
+            APBPrescaler::DividedBy16 => 16,
+        };
+
+        self.hclk_freq() / apb2_div
+    }
+
+    /// PLLI2S Q output after the PLLI2SDIVQ divider, feeding the SAI blocks.
+    #[inline]
+    pub fn plli2sq_freq(&self) -> Hertz {
+        let plli2scfgr = self.plli2scfgr().read();
+        let divq = (self.dckcfgr().read().plli2sdivq().bits() as u32) + 1;
+
+        self.plli2s_vco_freq() / (plli2scfgr.plli2sq().bits() as u32) / divq
+    }
+
+    /// PLLI2S R output, feeding the I2S peripherals.
+    #[inline]
+    pub fn plli2sr_freq(&self) -> Hertz {
+        self.plli2s_vco_freq() / (self.plli2scfgr().read().plli2sr().bits() as u32)
+    }
+
+    /// I2S kernel clock, `None` when taken from the external I2S_CKIN pin.
+    #[inline]
+    pub fn i2s_freq(&self) -> Option<Hertz> {
+        match self.i2s_clock_source() {
+            I2SClockSource::PLLI2S => Some(self.plli2sr_freq()),
+            I2SClockSource::External => None,
+        }
+    }
+
+    /// SAI block kernel clock, `None` when taken from the external I2S_CKIN pin.
+    #[inline]
+    pub fn sai_freq(&self, block: SAIBlock) -> Option<Hertz> {
+        match self.sai_clock_source(block) {
+            SAIClockSource::PLLSAI => Some(self.pllsaiq_freq()),
+            SAIClockSource::PLLI2S => Some(self.plli2sq_freq()),
+            SAIClockSource::External => None,
+        }
+    }
+
+    /// VCO input frequency shared by the main PLL, PLLI2S and PLLSAI (PLL input / PLLM).
+    #[inline]
+    pub fn pll_vco_input_freq(&self) -> Hertz {
+        let freq = match self.pll_clock_source() {
+            PLLClockSource::HSI => HSI_FREQ,
+            PLLClockSource::HSE => hse_freq(),
+        };
+
+        Hertz::from_raw(freq.raw().saturating_div(self.pll_division_factor() as u32))
+    }
+
+    #[inline]
+    fn pll_vco_freq(&self) -> Hertz {
+        let plln = self.pll_multiplication_factor() as u32;
+
+        Hertz::from_raw(self.pll_vco_input_freq().raw().saturating_mul(plln))
+    }
+
+    /// RTC clock, `None` if no clock is selected.
+    #[inline]
+    pub fn rtc_freq(&self) -> Option<Hertz> {
+        match self.rtc_clock_source() {
+            RTCClockSource::NoClock => None,
+            RTCClockSource::LSE => Some(LSE_FREQ),
+            RTCClockSource::LSI => Some(LSI_FREQ),
+            RTCClockSource::HSE => {
+                // RTCPRE values 0 and 1 feed no clock to the RTC
+                match self.cfgr().read().rtcpre().bits() as u32 {
+                    0 | 1 => None,
+                    prescaler => Some(hse_freq() / prescaler),
+                }
+            }
+        }
+    }
+
+    /// Kernel clock of TIM2-7 and TIM12-14.
+    #[inline]
+    pub fn apb1_timer_freq(&self) -> Hertz {
+        self.timer_clock_multiplier().timer_freq(
+            self.hclk_freq(),
+            self.pclk1_freq(),
+            self.apb1_prescaler()
+        )
+    }
+
+    /// Kernel clock of TIM1, TIM8 and TIM9-11.
+    #[inline]
+    pub fn apb2_timer_freq(&self) -> Hertz {
+        self.timer_clock_multiplier().timer_freq(
+            self.hclk_freq(),
+            self.pclk2_freq(),
+            self.apb2_prescaler()
+        )
+    }
+
+    #[inline]
+    fn plli2s_vco_freq(&self) -> Hertz {
+        let plli2sn = self.plli2scfgr().read().plli2sn().bits() as u32;
+
+        Hertz::from_raw(self.pll_vco_input_freq().raw().saturating_mul(plli2sn))
+    }
+
+    #[inline]
+    fn pllsaiq_freq(&self) -> Hertz {
+        let divq = (self.dckcfgr().read().pllsaidivq().bits() as u32) + 1;
+
+        self.pllsai_vco_freq() / (self.pllsaicfgr().read().pllsaiq().bits() as u32) / divq
+    }
+
+    #[inline]
+    fn pllsai_vco_freq(&self) -> Hertz {
+        let pllsain = self.pllsaicfgr().read().pllsain().bits() as u32;
+
+        Hertz::from_raw(self.pll_vco_input_freq().raw().saturating_mul(pllsain))
+    }
+
+    #[inline]
+    pub fn lcd_clock_division_factor(&self) -> LCDClockDivisionFactor {
+        LCDClockDivisionFactor::from_bits(self.dckcfgr().read().pllsaidivr().bits() as _)
+    }
+
+    /// PLLSAI R output, before the PLLSAIDIVR divider.
+    #[inline]
+    pub fn pllsai_freq(&self) -> Hertz {
+        self.pllsai_vco_freq() / (self.pllsaicfgr().read().pllsair().bits() as u32)
+    }
+
+    /// LCD-TFT pixel clock (LCD_CLK), PLLSAI R output divided by PLLSAIDIVR.
+    #[inline]
+    pub fn lcd_pixel_clock_freq(&self) -> Hertz {
+        self.pllsai_freq() / self.lcd_clock_division_factor().division_factor()
+    }
+
+    fn lcdclk_freq(&self) -> Option<Hertz> {
+        if self.cr().read().pllsairdy().bit_is_clear() {
+            return None;
+        }
+
+        Some(self.lcd_pixel_clock_freq())
+    }
+}
+
+/// Frozen clock frequencies returned by [`RCC::configure_system_clock`].
+///
+/// Drivers take this by reference so that their timing is derived from the clock tree
+/// the application configured instead of re-reading the RCC registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clocks {
+    sysclk: Hertz,
+    hclk: Hertz,
+    pclk1: Hertz,
+    pclk2: Hertz,
+    timclk1: Hertz,
+    timclk2: Hertz,
+    pll48clk: Option<Hertz>,
+    lcdclk: Option<Hertz>,
+}
+
+impl Clocks {
+    #[inline]
+    pub const fn sysclk(&self) -> Hertz {
+        self.sysclk
+    }
+
+    #[inline]
+    pub const fn hclk(&self) -> Hertz {
+        self.hclk
+    }
+
+    #[inline]
+    pub const fn pclk1(&self) -> Hertz {
+        self.pclk1
+    }
+
+    #[inline]
+    pub const fn pclk2(&self) -> Hertz {
+        self.pclk2
+    }
+
+    /// Kernel clock of the timers on APB1
+    #[inline]
+    pub const fn timclk1(&self) -> Hertz {
+        self.timclk1
+    }
+
+    /// Kernel clock of the timers on APB2
+    #[inline]
+    pub const fn timclk2(&self) -> Hertz {
+        self.timclk2
+    }
+
+    /// PLL Q output feeding USB OTG FS, SDIO and RNG, if the main PLL is running
+    #[inline]
+    pub const fn pll48clk(&self) -> Option<Hertz> {
+        self.pll48clk
+    }
+
+    /// LCD-TFT clock derived from PLLSAI, if PLLSAI is running
+    #[inline]
+    pub const fn lcdclk(&self) -> Option<Hertz> {
+        self.lcdclk
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SystemClockConfig {
+    HSI(u8),
+    HSE(HSEConfig),
+    PLL(PLLConfig),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HSEConfig {
+    /// Crystal or external clock frequency
+    pub freq: Hertz,
+    pub mode: HSEMode,
+    /// Enable the Clock Security System once HSE is ready
+    pub css: bool,
+}
+
+impl HSEConfig {
+    /// Crystal or ceramic resonator between OSC_IN and OSC_OUT.
+    pub const fn oscillator(freq: Hertz) -> Self {
+        Self { freq, mode: HSEMode::Oscillator, css: false }
+    }
+
+    /// External clock signal fed into OSC_IN.
+    pub const fn bypass(freq: Hertz) -> Self {
+        Self { freq, mode: HSEMode::Bypass, css: false }
+    }
+
+    const fn is_valid(&self) -> bool {
+        let freq = self.freq.raw();
+
+        match self.mode {
+            HSEMode::Oscillator => freq >= HSE_OSC_FREQ_MIN && freq <= HSE_OSC_FREQ_MAX,
+            HSEMode::Bypass => freq >= HSE_BYPASS_FREQ_MIN && freq <= HSE_BYPASS_FREQ_MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HSEMode {
+    Oscillator,
+    Bypass,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PLLSource {
+    HSI,
+    HSE(HSEConfig),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PLLConfig {
+    pub clock_source: PLLSource,
+    pub pllm: u8,
+    pub plln: u16,
+    pub pllq: u8,
+    pub system_clock_div_factor: PLLSysClockDivisionFactor,
+}
+
+impl PLLConfig {
+    /// SYSCLK frequency produced by this configuration.
+    pub const fn sysclk_freq(&self) -> Hertz {
+        let input_freq = match self.clock_source {
+            PLLSource::HSI => HSI_FREQ.raw(),
+            PLLSource::HSE(hse) => hse.freq.raw(),
+        };
+        let pllp = match self.system_clock_div_factor {
+            PLLSysClockDivisionFactor::DividedBy2 => 2,
+            PLLSysClockDivisionFactor::DividedBy4 => 4,
+            PLLSysClockDivisionFactor::DividedBy6 => 6,
+            PLLSysClockDivisionFactor::DividedBy8 => 8,
+        };
+
+        Hertz::from_raw(
+            (((input_freq as u64) * (self.plln as u64)) / (self.pllm as u64) / (pllp as u64)) as u32
+        )
+    }
+
+    /// Starts a PLL solver fed from the 16 MHz internal oscillator.
+    pub const fn hsi() -> PLLConfigBuilder {
+        PLLConfigBuilder::new(PLLSource::HSI)
+    }
+
+    /// Starts a PLL solver fed from the external oscillator described by `config`.
+    pub const fn hse(config: HSEConfig) -> PLLConfigBuilder {
+        PLLConfigBuilder::new(PLLSource::HSE(config))
+    }
+}
+
+/// Searches the legal PLL ranges for a configuration producing the requested
+/// frequencies.
+///
+/// All methods are `const`, so a configuration can be checked at build time:
+///
+/// ```ignore
+/// const PLL: PLLConfig = PLLConfig::hse(HSEConfig::oscillator(Hertz::MHz(8)))
+///     .sysclk(Hertz::MHz(168))
+///     .pll48clk(Hertz::MHz(48))
+///     .build_or_panic();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PLLConfigBuilder {
+    clock_source: PLLSource,
+    sysclk_freq: u32,
+    pll48_freq: Option<u32>,
+}
+
+impl PLLConfigBuilder {
+    const PLLM_MIN: u32 = 2;
+    const PLLM_MAX: u32 = 63;
+    const PLLN_MIN: u32 = 50;
+    const PLLN_MAX: u32 = 432;
+    const PLLQ_MIN: u32 = 2;
+    const PLLQ_MAX: u32 = 15;
+
+    const VCO_INPUT_MIN: u32 = 1_000_000;
+    const VCO_INPUT_MAX: u32 = 2_000_000;
+    const VCO_OUTPUT_MIN: u32 = 100_000_000;
+    const VCO_OUTPUT_MAX: u32 = 432_000_000;
+    const SYSCLK_MAX: u32 = 180_000_000;
+    const PLL48CLK_MAX: u32 = 48_000_000;
+
+    const fn new(clock_source: PLLSource) -> Self {
+        Self {
+            clock_source,
+            sysclk_freq: 0,
+            pll48_freq: None,
+        }
+    }
+
+    /// Target system clock frequency.
+    pub const fn sysclk(mut self, freq: Hertz) -> Self {
+        self.sysclk_freq = freq.raw();
+        self
+    }
+
+    /// Exact PLL48CLK (USB OTG FS, SDIO, RNG) frequency.
+    ///
+    /// When not set, the smallest PLLQ keeping PLL48CLK at or below 48 MHz is chosen.
+    pub const fn pll48clk(mut self, freq: Hertz) -> Self {
+        self.pll48_freq = Some(freq.raw());
+        self
+    }
+
+    pub const fn build(self) -> core::result::Result<PLLConfig, PLLError> {
+        let input_freq = match self.clock_source {
+            PLLSource::HSI => HSI_FREQ.raw(),
+            PLLSource::HSE(hse) => {
+                if !hse.is_valid() {
+                    return Err(PLLError::InputFrequencyOutOfRange);
+                }
+                hse.freq.raw()
+            }
+        };
+
+        if self.sysclk_freq == 0 || self.sysclk_freq > Self::SYSCLK_MAX {
+            return Err(PLLError::SysClockOutOfRange);
+        }
+
+        if let Some(freq) = self.pll48_freq {
+            if freq == 0 || freq > Self::PLL48CLK_MAX {
+                return Err(PLLError::PLL48ClockOutOfRange);
+            }
+        }
+
+        let input = input_freq as u64;
+        let mut pll48_unreachable = false;
+
+        // Walking PLLM upwards tries the highest VCO input frequency first,
+        // which gives the lowest PLL jitter.
+        let mut m = Self::PLLM_MIN;
+        while m <= Self::PLLM_MAX {
+            let vco_in_ok =
+                input_freq >= Self::VCO_INPUT_MIN * m &&
+                input_freq <= Self::VCO_INPUT_MAX * m;
+
+            if vco_in_ok {
+                let mut p = 2;
+                while p <= 8 {
+                    let vco = (self.sysclk_freq as u64) * (p as u64);
+                    let n = (vco * (m as u64)) / input;
+
+                    let vco_ok =
+                        vco >= (Self::VCO_OUTPUT_MIN as u64) &&
+                        vco <= (Self::VCO_OUTPUT_MAX as u64);
+                    let n_ok =
+                        (vco * (m as u64)).is_multiple_of(input) &&
+                        n >= (Self::PLLN_MIN as u64) &&
+                        n <= (Self::PLLN_MAX as u64);
+
+                    if vco_ok && n_ok {
+                        match Self::solve_pllq(vco, self.pll48_freq) {
+                            Some(q) => {
+                                return Ok(PLLConfig {
+                                    clock_source: self.clock_source,
+                                    pllm: m as u8,
+                                    plln: n as u16,
+                                    pllq: q as u8,
+                                    system_clock_div_factor: PLLSysClockDivisionFactor::from_div(
+                                        p
+                                    ),
+                                });
+                            }
+                            None => {
+                                pll48_unreachable = true;
+                            }
+                        }
+                    }
+
+                    p += 2;
+                }
+            }
+
+            m += 1;
+        }
+
+        if pll48_unreachable {
+            Err(PLLError::PLL48ClockUnreachable)
+        } else {
+            Err(PLLError::NoSolution)
+        }
+    }
+
+    /// Same as [`build`](Self::build) but panics on error, which turns an invalid
+    /// configuration into a build failure when evaluated in a `const` context.
+    pub const fn build_or_panic(self) -> PLLConfig {
+        match self.build() {
+            Ok(config) => config,
+            Err(e) => panic!("{}", e.as_str()),
+        }
+    }
+
+    const fn solve_pllq(vco: u64, pll48_freq: Option<u32>) -> Option<u32> {
+        let q = match pll48_freq {
+            Some(freq) => {
+                if !vco.is_multiple_of(freq as u64) {
+                    return None;
+                }
+                vco / (freq as u64)
+            }
+            None => vco.div_ceil(Self::PLL48CLK_MAX as u64),
+        };
+
+        if q < (Self::PLLQ_MIN as u64) {
+            match pll48_freq {
+                Some(_) => None,
+                None => Some(Self::PLLQ_MIN),
+            }
+        } else if q > (Self::PLLQ_MAX as u64) {
+            None
+        } else {
+            Some(q as u32)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PLLError {
+    /// HSE is outside 4–26 MHz (oscillator) or 1–50 MHz (bypass)
+    InputFrequencyOutOfRange,
+    /// SYSCLK target is zero or above 180 MHz
+    SysClockOutOfRange,
+    /// PLL48CLK target is zero or above 48 MHz
+    PLL48ClockOutOfRange,
+    /// SYSCLK is reachable but not together with the requested PLL48CLK
+    PLL48ClockUnreachable,
+    /// No PLLM/PLLN/PLLP combination yields the requested SYSCLK
+    NoSolution,
+}
+
+impl PLLError {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            PLLError::InputFrequencyOutOfRange => "PLL input frequency out of range",
+            PLLError::SysClockOutOfRange => "SYSCLK frequency out of range",
+            PLLError::PLL48ClockOutOfRange => "PLL48CLK frequency out of range",
+            PLLError::PLL48ClockUnreachable => "PLL48CLK frequency unreachable",
+            PLLError::NoSolution => "no PLL configuration for requested SYSCLK",
+        }
+    }
+}
+
+impl fmt::Display for PLLError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PLLSAIConfig {
+    pub pllsain: u16,
+    pub pllsaiq: u8,
+    pub pllsair: u8,
+    pub lcd_div_factor: LCDClockDivisionFactor,
+}
+
+impl PLLSAIConfig {
+    const PLLSAIN_MIN: u32 = 50;
+    const PLLSAIN_MAX: u32 = 432;
+    const PLLSAIR_MIN: u32 = 2;
+    const PLLSAIR_MAX: u32 = 7;
+    const PLLSAIQ_MIN: u32 = 2;
+    const PLLSAIQ_MAX: u32 = 15;
+    const PLLSAIQ_RESET: u8 = 4;
+
+    /// Searches PLLSAI N/R and the PLLSAIDIVR factor for the LCD pixel clock closest to
+    /// `pixel_clock`.
+    ///
+    /// `vco_input_freq` is the PLL input divided by PLLM, see [`RCC::pll_vco_input_freq`].
+    /// PLLSAIQ is left at its reset value.
+    pub const fn for_pixel_clock(
+        vco_input_freq: Hertz,
+        pixel_clock: Hertz
+    ) -> core::result::Result<PixelClockConfig, PLLError> {
+        let vco_input_freq = vco_input_freq.raw();
+        let pixel_clock = pixel_clock.raw();
+
+        if
+            vco_input_freq < PLLConfigBuilder::VCO_INPUT_MIN ||
+            vco_input_freq > PLLConfigBuilder::VCO_INPUT_MAX
+        {
+            return Err(PLLError::InputFrequencyOutOfRange);
+        }
+
+        if pixel_clock == 0 {
+            return Err(PLLError::NoSolution);
+        }
+
+        const DIVR: [LCDClockDivisionFactor; 4] = [
+            LCDClockDivisionFactor::DividedBy2,
+            LCDClockDivisionFactor::DividedBy4,
+            LCDClockDivisionFactor::DividedBy8,
+            LCDClockDivisionFactor::DividedBy16,
+        ];
+
+        let mut best: Option<PixelClockConfig> = None;
+
+        let mut n = Self::PLLSAIN_MIN;
+        while n <= Self::PLLSAIN_MAX {
+            let vco = vco_input_freq * n;
+
+            if
+                vco >= PLLConfigBuilder::VCO_OUTPUT_MIN &&
+                vco <= PLLConfigBuilder::VCO_OUTPUT_MAX
+            {
+                let mut r = Self::PLLSAIR_MIN;
+                while r <= Self::PLLSAIR_MAX {
+                    let mut i = 0;
+                    while i < DIVR.len() {
+                        let freq = vco / r / DIVR[i].division_factor();
+                        let error = freq.abs_diff(pixel_clock);
+
+                        let better = match best {
+                            Some(ref b) => error < b.error.raw(),
+                            None => true,
+                        };
+                        if better {
+                            best = Some(PixelClockConfig {
+                                pllsai: PLLSAIConfig {
+                                    pllsain: n as u16,
+                                    pllsaiq: Self::PLLSAIQ_RESET,
+                                    pllsair: r as u8,
+                                    lcd_div_factor: DIVR[i],
+                                },
+                                freq: Hertz::from_raw(freq),
+                                error: Hertz::from_raw(error),
+                            });
+                        }
+
+                        i += 1;
+                    }
+
+                    r += 1;
+                }
+            }
+
+            n += 1;
+        }
+
+        match best {
+            Some(best) => Ok(best),
+            None => Err(PLLError::NoSolution),
+        }
+    }
+
+    /// Whether N, Q and R are in range and the VCO, fed with `vco_input_freq`, stays
+    /// within 100 to 432 MHz.
+    pub const fn is_valid(&self, vco_input_freq: Hertz) -> bool {
+        let (n, q, r) = (self.pllsain as u32, self.pllsaiq as u32, self.pllsair as u32);
+        let vco = vco_input_freq.raw() as u64 * n as u64;
+
+        n >= Self::PLLSAIN_MIN &&
+            n <= Self::PLLSAIN_MAX &&
+            q >= Self::PLLSAIQ_MIN &&
+            q <= Self::PLLSAIQ_MAX &&
+            r >= Self::PLLSAIR_MIN &&
+            r <= Self::PLLSAIR_MAX &&
+            vco >= (PLLConfigBuilder::VCO_OUTPUT_MIN as u64) &&
+            vco <= (PLLConfigBuilder::VCO_OUTPUT_MAX as u64)
+    }
+}
+
+/// Result of [`PLLSAIConfig::for_pixel_clock`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PixelClockConfig {
+    pub pllsai: PLLSAIConfig,
+    /// Achieved pixel clock
+    pub freq: Hertz,
+    /// Absolute difference to the requested pixel clock
+    pub error: Hertz,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PLLI2SConfig {
+    pub plli2sn: u16,
+    pub plli2sq: u8,
+    pub plli2sr: u8,
+    /// PLLI2SDIVQ, division of the Q output towards SAI, 1 to 32
+    pub sai_div_factor: u8,
+}
+
+impl PLLI2SConfig {
+    const PLLI2SN_MIN: u32 = 50;
+    const PLLI2SN_MAX: u32 = 432;
+    const PLLI2SQ_MIN: u32 = 2;
+    const PLLI2SQ_MAX: u32 = 15;
+    const PLLI2SR_MIN: u32 = 2;
+    const PLLI2SR_MAX: u32 = 7;
+    const PLLI2SDIVQ_MAX: u32 = 32;
+    const I2S_PRESCALER_MIN: u32 = 4;
+    const I2S_PRESCALER_MAX: u32 = 511;
+    const I2SCLK_MAX: u32 = 192_000_000;
+    const MCLK_RATIO: u32 = 256;
+
+    /// Searches PLLI2S settings producing an audio clock for `sample_rate` with the
+    /// master clock (256 × Fs) enabled, on both the I2S (R) and SAI (Q) outputs.
+    ///
+    /// `vco_input_freq` is the PLL input divided by PLLM, see [`RCC::pll_vco_input_freq`].
+    /// The 44.1 kHz family cannot be produced exactly, so the combination with the
+    /// smallest error is returned together with the achieved sample rates.
+    pub const fn for_sample_rate(
+        vco_input_freq: Hertz,
+        sample_rate: Hertz
+    ) -> core::result::Result<AudioClockConfig, PLLError> {
+        let vco_input_freq = vco_input_freq.raw();
+        let sample_rate = sample_rate.raw();
+
+        if
+            vco_input_freq < PLLConfigBuilder::VCO_INPUT_MIN ||
+            vco_input_freq > PLLConfigBuilder::VCO_INPUT_MAX
+        {
+            return Err(PLLError::InputFrequencyOutOfRange);
+        }
+
+        if sample_rate == 0 {
+            return Err(PLLError::NoSolution);
+        }
+
+        // Sample rates are compared in mHz to keep the 44.1 kHz family precise
+        let target = (sample_rate as u64) * 1000;
+        let mclk = (sample_rate as u64) * (Self::MCLK_RATIO as u64);
 
-    #[inline]
-    pub fn sysclk_freq(&self) -> u32 {
-        match self.sysclock_clock_source() {
-            SystemClockSource::HSI => 16_000_000u32,
-            SystemClockSource::HSE => EXTERNAL_OSC_FREQ.get(),
-            SystemClockSource::PLL => {
-                let freq = match self.pll_clock_source() {
-                    PLLClockSource::HSI => 16_000_000u32,
-                    PLLClockSource::HSE => EXTERNAL_OSC_FREQ.get(),
-                };
+        // (plli2sn, plli2sr, i2s prescaler, error)
+        let mut best: Option<(u32, u32, u32, u64)> = None;
 
-                let pllp = match self.pll_sysclock_division_factor() {
-                    PLLSysClockDivisionFactor::DividedBy2 => 2,
-                    PLLSysClockDivisionFactor::DividedBy4 => 4,
-                    PLLSysClockDivisionFactor::DividedBy6 => 6,
-                    PLLSysClockDivisionFactor::DividedBy8 => 8,
-                };
+        let mut n = Self::PLLI2SN_MIN;
+        while n <= Self::PLLI2SN_MAX {
+            let vco = (vco_input_freq as u64) * (n as u64);
+
+            if
+                vco >= (PLLConfigBuilder::VCO_OUTPUT_MIN as u64) &&
+                vco <= (PLLConfigBuilder::VCO_OUTPUT_MAX as u64)
+            {
+                let mut r = Self::PLLI2SR_MIN;
+                while r <= Self::PLLI2SR_MAX {
+                    let i2sclk = vco / (r as u64);
+
+                    if i2sclk <= (Self::I2SCLK_MAX as u64) {
+                        let p = Self::clamp(
+                            (i2sclk + mclk / 2) / mclk,
+                            Self::I2S_PRESCALER_MIN,
+                            Self::I2S_PRESCALER_MAX
+                        );
+                        let error = ((i2sclk * 1000) /
+                            (Self::MCLK_RATIO as u64) /
+                            (p as u64)).abs_diff(target);
 
-                freq.saturating_div(self.pll_division_factor() as u32)
-                    .saturating_mul(self.pll_multiplication_factor() as u32)
-                    .saturating_div(pllp)
+                        let better = match best {
+                            Some((_, _, _, e)) => error < e,
+                            None => true,
+                        };
+                        if better {
+                            best = Some((n, r, p, error));
+                        }
+                    }
+
+                    r += 1;
+                }
             }
+
+            n += 1;
         }
-    }
 
-    #[inline]
-    pub fn hclk_freq(&self) -> u32 {
-        let ahb_div = match self.ahb_prescaler() {
-            AHBPrescaler::NotDivided => 1,
-            AHBPrescaler::DividedBy2 => 2,
-            AHBPrescaler::DividedBy4 => 4,
-            AHBPrescaler::DividedBy8 => 8,
-            AHBPrescaler::DividedBy16 => 16,
-            AHBPrescaler::DividedBy64 => 64,
-            AHBPrescaler::DividedBy128 => 128,
-            AHBPrescaler::DividedBy256 => 256,
-            AHBPrescaler::DividedBy512 => 512,
+        let (n, r, p, _) = match best {
+            Some(best) => best,
+            None => {
+                return Err(PLLError::NoSolution);
+            }
         };
 
-        self.sysclk_freq() / ahb_div
-    }
+        let vco = (vco_input_freq as u64) * (n as u64);
 
-    #[inline]
-    pub fn pclk1_freq(&self) -> u32 {
-        let apb1_div = match self.apb1_prescaler() {
-            APBPrescaler::NotDivided => 1,
-            APBPrescaler::DividedBy2 => 2,
-            APBPrescaler::DividedBy4 => 4,
-            APBPrescaler::DividedBy8 => 8,
-            APBPrescaler::DividedBy16 => 16,
-        };
+        // (plli2sq, plli2sdivq, error)
+        let mut sai: (u32, u32, u64) = (Self::PLLI2SQ_MIN, 1, u64::MAX);
 
-        self.hclk_freq() / apb1_div
-    }
+        let mut q = Self::PLLI2SQ_MIN;
+        while q <= Self::PLLI2SQ_MAX {
+            let qclk = vco / (q as u64);
+            let divq = Self::clamp((qclk + mclk / 2) / mclk, 1, Self::PLLI2SDIVQ_MAX);
+            let error = ((qclk * 1000) / (divq as u64) / (Self::MCLK_RATIO as u64)).abs_diff(
+                target
+            );
 
-    #[inline]
-    pub fn pclk2_freq(&self) -> u32 {
-        let apb2_div = match self.apb2_prescaler() {
-            APBPrescaler::NotDivided => 1,
-            APBPrescaler::DividedBy2 => 2,
-            APBPrescaler::DividedBy4 => 4,
-            APBPrescaler::DividedBy8 => 8,
-            APBPrescaler::DividedBy16 => 16,
-        };
+            if error < sai.2 {
+                sai = (q, divq, error);
+            }
 
-        self.hclk_freq() / apb2_div
+            q += 1;
+        }
+
+        let (q, divq, _) = sai;
+
+        Ok(AudioClockConfig {
+            plli2s: PLLI2SConfig {
+                plli2sn: n as u16,
+                plli2sq: q as u8,
+                plli2sr: r as u8,
+                sai_div_factor: divq as u8,
+            },
+            i2sdiv: (p / 2) as u8,
+            i2sodd: p % 2 == 1,
+            i2s_sample_rate: Hertz::from_raw(
+                (vco / (r as u64) / (Self::MCLK_RATIO as u64) / (p as u64)) as u32
+            ),
+            sai_sample_rate: Hertz::from_raw(
+                (vco / (q as u64) / (divq as u64) / (Self::MCLK_RATIO as u64)) as u32
+            ),
+        })
     }
-}
 
-pub enum SystemClockConfig {
-    HSI(u8),
-    HSE(u16),
-    PLL(PLLConfig),
-}
+    const fn is_valid(&self) -> bool {
+        let (n, q, r, divq) = (
+            self.plli2sn as u32,
+            self.plli2sq as u32,
+            self.plli2sr as u32,
+            self.sai_div_factor as u32,
+        );
 
-pub struct PLLConfig {
-    pub clock_source: PLLClockSource,
-    pub pllm: u8,
-    pub plln: u16,
-    pub pllq: u8,
-    pub system_clock_div_factor: PLLSysClockDivisionFactor,
+        n >= Self::PLLI2SN_MIN &&
+            n <= Self::PLLI2SN_MAX &&
+            q >= Self::PLLI2SQ_MIN &&
+            q <= Self::PLLI2SQ_MAX &&
+            r >= Self::PLLI2SR_MIN &&
+            r <= Self::PLLI2SR_MAX &&
+            divq >= 1 &&
+            divq <= Self::PLLI2SDIVQ_MAX
+    }
+
+    const fn clamp(val: u64, min: u32, max: u32) -> u32 {
+        if val < (min as u64) {
+            min
+        } else if val > (max as u64) {
+            max
+        } else {
+            val as u32
+        }
+    }
 }
 
-pub struct PLLSAIConfig {
-    pub pllsain: u16,
-    pub pllsaiq: u8,
-    pub pllsair: u8,
-    pub lcd_div_factor: LCDClockDivisionFactor,
+/// Result of [`PLLI2SConfig::for_sample_rate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioClockConfig {
+    pub plli2s: PLLI2SConfig,
+    /// I2SDIV value for SPI_I2SPR
+    pub i2sdiv: u8,
+    /// ODD value for SPI_I2SPR
+    pub i2sodd: bool,
+    /// Sample rate achieved by the I2S peripherals
+    pub i2s_sample_rate: Hertz,
+    /// Sample rate achieved by the SAI blocks without master clock division
+    pub sai_sample_rate: Hertz,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BUSConfig {
     pub ahb_prescaler: AHBPrescaler,
     pub apb1_prescaler: APBPrescaler,
@@ -293,6 +1629,263 @@ impl PLLSysClockDivisionFactor {
             _ => panic!(),
         }
     }
+
+    pub const fn from_div(div: u32) -> Self {
+        match div {
+            2 => Self::DividedBy2,
+            4 => Self::DividedBy4,
+            6 => Self::DividedBy6,
+            8 => Self::DividedBy8,
+            _ => panic!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bus {
+    AHB1,
+    AHB2,
+    AHB3,
+    APB1,
+    APB2,
+}
+
+/// Position of a peripheral in the RCC clock gating registers. The bit is the same in
+/// the RSTR, ENR and LPENR register of the bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeripheralClock {
+    pub bus: Bus,
+    pub bit: u8,
+}
+
+impl PeripheralClock {
+    pub const fn new(bus: Bus, bit: u8) -> Self {
+        Self { bus, bit }
+    }
+}
+
+/// Gating position of every peripheral handled by this crate, referred to by the
+/// [`Peripheral::CLOCK`] of its handle.
+impl PeripheralClock {
+    pub const GPIOA: Self = Self::new(Bus::AHB1, 0);
+    pub const GPIOB: Self = Self::new(Bus::AHB1, 1);
+    pub const GPIOC: Self = Self::new(Bus::AHB1, 2);
+    pub const GPIOD: Self = Self::new(Bus::AHB1, 3);
+    pub const GPIOE: Self = Self::new(Bus::AHB1, 4);
+    pub const GPIOF: Self = Self::new(Bus::AHB1, 5);
+    pub const GPIOG: Self = Self::new(Bus::AHB1, 6);
+    pub const GPIOH: Self = Self::new(Bus::AHB1, 7);
+    pub const GPIOI: Self = Self::new(Bus::AHB1, 8);
+    pub const GPIOJ: Self = Self::new(Bus::AHB1, 9);
+    pub const GPIOK: Self = Self::new(Bus::AHB1, 10);
+
+    pub const SPI2: Self = Self::new(Bus::APB1, 14);
+    pub const SPI3: Self = Self::new(Bus::APB1, 15);
+    pub const I2C1: Self = Self::new(Bus::APB1, 21);
+    pub const I2C2: Self = Self::new(Bus::APB1, 22);
+    pub const I2C3: Self = Self::new(Bus::APB1, 23);
+    pub const PWR: Self = Self::new(Bus::APB1, 28);
+
+    pub const SPI1: Self = Self::new(Bus::APB2, 12);
+    pub const SPI4: Self = Self::new(Bus::APB2, 13);
+    pub const SYSCFG: Self = Self::new(Bus::APB2, 14);
+    pub const SPI5: Self = Self::new(Bus::APB2, 20);
+    pub const SPI6: Self = Self::new(Bus::APB2, 21);
+    pub const LTDC: Self = Self::new(Bus::APB2, 26);
+}
+
+/// Clock gating registers of a bus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Gate {
+    Reset,
+    Enable,
+    EnableInSleep,
+}
+
+/// Reset flags of RCC_CSR.
+///
+/// A power-on reset also sets `pin` and `brown_out`, so check `power_on` first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResetReason {
+    pub brown_out: bool,
+    pub pin: bool,
+    pub power_on: bool,
+    pub software: bool,
+    pub independent_watchdog: bool,
+    pub window_watchdog: bool,
+    pub low_power: bool,
+}
+
+impl ResetReason {
+    pub const fn from_bits(csr: u32) -> Self {
+        Self {
+            brown_out: csr & (1 << 25) != 0,
+            pin: csr & (1 << 26) != 0,
+            power_on: csr & (1 << 27) != 0,
+            software: csr & (1 << 28) != 0,
+            independent_watchdog: csr & (1 << 29) != 0,
+            window_watchdog: csr & (1 << 30) != 0,
+            low_power: csr & (1 << 31) != 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MCO1Source {
+    HSI = 0b00,
+    LSE = 0b01,
+    HSE = 0b10,
+    PLL = 0b11,
+}
+
+impl MCO1Source {
+    pub fn from_bits(val: u32) -> Self {
+        match val {
+            0b00 => Self::HSI,
+            0b01 => Self::LSE,
+            0b10 => Self::HSE,
+            0b11 => Self::PLL,
+            _ => panic!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MCO2Source {
+    SYSCLK = 0b00,
+    PLLI2S = 0b01,
+    HSE = 0b10,
+    PLL = 0b11,
+}
+
+impl MCO2Source {
+    pub fn from_bits(val: u32) -> Self {
+        match val {
+            0b00 => Self::SYSCLK,
+            0b01 => Self::PLLI2S,
+            0b10 => Self::HSE,
+            0b11 => Self::PLL,
+            _ => panic!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MCOPrescaler {
+    NotDivided = 0b000,
+    DividedBy2 = 0b100,
+    DividedBy3 = 0b101,
+    DividedBy4 = 0b110,
+    DividedBy5 = 0b111,
+}
+
+impl MCOPrescaler {
+    pub fn from_bits(val: u32) -> Self {
+        match val {
+            0b100 => Self::DividedBy2,
+            0b101 => Self::DividedBy3,
+            0b110 => Self::DividedBy4,
+            0b111 => Self::DividedBy5,
+            _ => Self::NotDivided,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LSEMode {
+    Oscillator,
+    Bypass,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RTCClockSource {
+    NoClock = 0b00,
+    LSE = 0b01,
+    LSI = 0b10,
+    HSE = 0b11,
+}
+
+impl RTCClockSource {
+    pub fn from_bits(val: u32) -> Self {
+        match val {
+            0b00 => Self::NoClock,
+            0b01 => Self::LSE,
+            0b10 => Self::LSI,
+            0b11 => Self::HSE,
+            _ => panic!(),
+        }
+    }
+}
+
+/// Timer clock prescaler selection (DCKCFGR TIMPRE).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimerClockMultiplier {
+    /// Timers run at PCLK when the APB prescaler is 1, 2 × PCLK otherwise
+    X2 = 0b0,
+    /// Timers run at HCLK when the APB prescaler is 1, 2 or 4, 4 × PCLK otherwise
+    X4 = 0b1,
+}
+
+impl TimerClockMultiplier {
+    pub fn from_bits(val: u32) -> Self {
+        match val {
+            0b0 => Self::X2,
+            0b1 => Self::X4,
+            _ => panic!(),
+        }
+    }
+
+    pub const fn timer_freq(&self, hclk: Hertz, pclk: Hertz, apb_prescaler: APBPrescaler) -> Hertz {
+        match (self, apb_prescaler) {
+            (Self::X2, APBPrescaler::NotDivided) => pclk,
+            (Self::X2, _) => Hertz::from_raw(pclk.raw() * 2),
+            (
+                Self::X4,
+                APBPrescaler::NotDivided | APBPrescaler::DividedBy2 | APBPrescaler::DividedBy4,
+            ) => hclk,
+            (Self::X4, _) => Hertz::from_raw(pclk.raw() * 4),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum I2SClockSource {
+    PLLI2S = 0b0,
+    External = 0b1,
+}
+
+impl I2SClockSource {
+    pub fn from_bits(val: u32) -> Self {
+        match val {
+            0b0 => Self::PLLI2S,
+            0b1 => Self::External,
+            _ => panic!(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SAIBlock {
+    A,
+    B,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SAIClockSource {
+    PLLSAI = 0b00,
+    PLLI2S = 0b01,
+    External = 0b10,
+}
+
+impl SAIClockSource {
+    pub fn from_bits(val: u32) -> Self {
+        match val {
+            0b00 => Self::PLLSAI,
+            0b01 => Self::PLLI2S,
+            0b10 => Self::External,
+            _ => panic!(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -313,6 +1906,15 @@ impl LCDClockDivisionFactor {
             _ => panic!(),
         }
     }
+
+    pub const fn division_factor(&self) -> u32 {
+        match self {
+            Self::DividedBy2 => 2,
+            Self::DividedBy4 => 4,
+            Self::DividedBy8 => 8,
+            Self::DividedBy16 => 16,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -342,6 +1944,20 @@ impl AHBPrescaler {
             _ => Self::NotDivided,
         }
     }
+
+    pub const fn division_factor(&self) -> u32 {
+        match self {
+            Self::NotDivided => 1,
+            Self::DividedBy2 => 2,
+            Self::DividedBy4 => 4,
+            Self::DividedBy8 => 8,
+            Self::DividedBy16 => 16,
+            Self::DividedBy64 => 64,
+            Self::DividedBy128 => 128,
+            Self::DividedBy256 => 256,
+            Self::DividedBy512 => 512,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -373,4 +1989,333 @@ impl APBPrescaler {
             Self::DividedBy16 => 0b111,
         }
     }
+
+    pub const fn division_factor(&self) -> u32 {
+        match self {
+            Self::NotDivided => 1,
+            Self::DividedBy2 => 2,
+            Self::DividedBy4 => 4,
+            Self::DividedBy8 => 8,
+            Self::DividedBy16 => 16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    HsiNotReady,
+    HseNotReady,
+    LsiNotReady,
+    LseNotReady,
+    PllLockTimeout,
+    PllSaiLockTimeout,
+    PllI2SLockTimeout,
+    SwitchTimeout,
+    /// HSE frequency is outside the range of its [`HSEMode`]
+    HseFrequencyOutOfRange,
+    /// HSE is already running in the other [`HSEMode`]
+    HseModeMismatch,
+    /// Backup domain write protection could not be lifted
+    BackupDomainTimeout,
+    /// LSE did not stop after being disabled
+    LseStopTimeout,
+    /// Another RTC clock source is already selected, only a backup domain reset clears it
+    RtcClockSourceLocked,
+    /// PLLM or the PLL source would change under a running PLLSAI or PLLI2S
+    PllInputInUse,
+    /// A PLLI2S factor is outside its legal range
+    InvalidPllI2SConfig,
+    /// A PLLSAI factor or the resulting VCO frequency is outside its legal range
+    InvalidPllSaiConfig,
+    /// Regulator did not reach the selected voltage scale
+    VoltageScaleTimeout,
+    /// Regulator did not enter or leave over-drive mode
+    OverdriveTimeout,
+    /// Flash did not apply the new number of wait states
+    FlashLatencyTimeout,
+    /// Requested HCLK is above the limit of the supply voltage range
+    UnsupportedFrequency,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::HsiNotReady => f.write_str("HSI Not Ready"),
+            Error::HseNotReady => f.write_str("HSE Not Ready"),
+            Error::LsiNotReady => f.write_str("LSI Not Ready"),
+            Error::LseNotReady => f.write_str("LSE Not Ready"),
+            Error::PllLockTimeout => f.write_str("PLL Lock Timeout"),
+            Error::PllSaiLockTimeout => f.write_str("PLLSAI Lock Timeout"),
+            Error::PllI2SLockTimeout => f.write_str("PLLI2S Lock Timeout"),
+            Error::SwitchTimeout => f.write_str("System Clock Switch Timeout"),
+            Error::HseFrequencyOutOfRange => f.write_str("HSE Frequency Out Of Range"),
+            Error::HseModeMismatch => f.write_str("HSE Mode Mismatch"),
+            Error::BackupDomainTimeout => f.write_str("Backup Domain Timeout"),
+            Error::LseStopTimeout => f.write_str("LSE Stop Timeout"),
+            Error::RtcClockSourceLocked => f.write_str("RTC Clock Source Locked"),
+            Error::PllInputInUse => f.write_str("PLL Input In Use"),
+            Error::InvalidPllI2SConfig => f.write_str("Invalid PLLI2S Config"),
+            Error::InvalidPllSaiConfig => f.write_str("Invalid PLLSAI Config"),
+            Error::VoltageScaleTimeout => f.write_str("Voltage Scale Timeout"),
+            Error::OverdriveTimeout => f.write_str("Over-drive Timeout"),
+            Error::FlashLatencyTimeout => f.write_str("Flash Latency Timeout"),
+            Error::UnsupportedFrequency => f.write_str("Unsupported Frequency"),
+        }
+    }
+}
+
+pub type Result<T> = core::result::Result<T, Error>;
+
+#[inline]
+fn hse_freq() -> Hertz {
+    Hertz::from_raw(HSE_FREQ.load(Ordering::Relaxed))
+}
+
+#[inline]
+pub(crate) fn wait_until(ready: impl FnMut() -> bool, error: Error) -> Result<()> {
+    wait_until_timeout(READY_TIMEOUT, ready, error)
+}
+
+#[inline]
+fn wait_until_timeout(timeout: u32, mut ready: impl FnMut() -> bool, error: Error) -> Result<()> {
+    for _ in 0..timeout {
+        if ready() {
+            return Ok(());
+        }
+    }
+
+    Err(error)
+}
+
+/// Configuration last applied by [`RCC::configure_system_clock`], shared with the Stop-mode
+/// restore path, which may run from an interrupt handler.
+static SYSTEM_CLOCK_CONFIG: Mutex<Cell<Option<(SystemClockConfig, BUSConfig, VoltageRange)>>> =
+    Mutex::new(Cell::new(None));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ gpio::GPIOC, i2c::I2C3, spi::SPI5, time::RateExtU32 };
+
+    #[test]
+    fn pll_solver_hse_8mhz_168mhz() {
+        let config = PLLConfig::hse(HSEConfig::oscillator(8.MHz()))
+            .sysclk(168.MHz())
+            .pll48clk(48.MHz())
+            .build();
+
+        assert_eq!(
+            config,
+            Ok(PLLConfig {
+                clock_source: PLLSource::HSE(HSEConfig::oscillator(8.MHz())),
+                pllm: 4,
+                plln: 168,
+                pllq: 7,
+                system_clock_div_factor: PLLSysClockDivisionFactor::DividedBy2,
+            })
+        );
+    }
+
+    #[test]
+    fn pll_solver_hsi_180mhz() {
+        let config = PLLConfig::hsi().sysclk(180.MHz()).build().unwrap();
+
+        assert_eq!(config.pllm, 8);
+        assert_eq!(config.plln, 180);
+        assert_eq!(config.pllq, 8);
+        assert_eq!(config.system_clock_div_factor, PLLSysClockDivisionFactor::DividedBy2);
+    }
+
+    #[test]
+    fn pll_solver_hse_25mhz_low_sysclk() {
+        let config = PLLConfig::hse(HSEConfig::oscillator(25.MHz()))
+            .sysclk(48.MHz())
+            .pll48clk(48.MHz())
+            .build();
+
+        assert_eq!(
+            config,
+            Ok(PLLConfig {
+                clock_source: PLLSource::HSE(HSEConfig::oscillator(25.MHz())),
+                pllm: 25,
+                plln: 192,
+                pllq: 4,
+                system_clock_div_factor: PLLSysClockDivisionFactor::DividedBy4,
+            })
+        );
+    }
+
+    #[test]
+    fn pll_solver_errors() {
+        assert_eq!(
+            PLLConfig::hse(HSEConfig::oscillator(8.MHz()))
+                .sysclk(180.MHz())
+                .pll48clk(48.MHz())
+                .build(),
+            Err(PLLError::PLL48ClockUnreachable)
+        );
+        assert_eq!(
+            PLLConfig::hse(HSEConfig::oscillator(8.MHz())).sysclk(200.MHz()).build(),
+            Err(PLLError::SysClockOutOfRange)
+        );
+        assert_eq!(
+            PLLConfig::hse(HSEConfig::oscillator(30.MHz())).sysclk(168.MHz()).build(),
+            Err(PLLError::InputFrequencyOutOfRange)
+        );
+        assert_eq!(
+            PLLConfig::hse(HSEConfig::oscillator(8.MHz())).sysclk(168_000_001.Hz()).build(),
+            Err(PLLError::NoSolution)
+        );
+    }
+
+    #[test]
+    fn pll_solver_hse_bypass() {
+        let hse = HSEConfig::bypass(30.MHz());
+
+        assert!(PLLConfig::hse(hse).sysclk(120.MHz()).build().is_ok());
+        assert_eq!(
+            PLLConfig::hse(HSEConfig::bypass(60.MHz())).sysclk(120.MHz()).build(),
+            Err(PLLError::InputFrequencyOutOfRange)
+        );
+    }
+
+    #[test]
+    fn pll_config_sysclk_freq() {
+        let config = PLLConfig::hse(HSEConfig::oscillator(25.MHz()))
+            .sysclk(180.MHz())
+            .build()
+            .unwrap();
+
+        assert_eq!(config.sysclk_freq(), Hertz::MHz(180));
+    }
+
+    #[test]
+    fn plli2s_solver_48khz() {
+        let audio = PLLI2SConfig::for_sample_rate(1.MHz(), 48.kHz()).unwrap();
+
+        // 48 kHz is not exactly reachable with MCLK enabled, the best is 47.991 kHz
+        assert!(audio.i2s_sample_rate.raw().abs_diff(48_000) <= 9);
+        assert!(audio.sai_sample_rate.raw().abs_diff(48_000) <= 9);
+    }
+
+    #[test]
+    fn plli2s_solver_44_1khz() {
+        let audio = PLLI2SConfig::for_sample_rate(1.MHz(), 44_100.Hz()).unwrap();
+
+        assert!(audio.i2s_sample_rate.raw().abs_diff(44_100) <= 44);
+        assert!(audio.sai_sample_rate.raw().abs_diff(44_100) <= 44);
+        assert!((2..=7).contains(&audio.plli2s.plli2sr));
+        assert!((1..=32).contains(&audio.plli2s.sai_div_factor));
+    }
+
+    #[test]
+    fn plli2s_solver_errors() {
+        assert_eq!(
+            PLLI2SConfig::for_sample_rate(500.kHz(), 48.kHz()),
+            Err(PLLError::InputFrequencyOutOfRange)
+        );
+        assert_eq!(PLLI2SConfig::for_sample_rate(2.MHz(), 0.Hz()), Err(PLLError::NoSolution));
+    }
+
+    #[test]
+    fn plli2s_config_validation() {
+        let audio = PLLI2SConfig::for_sample_rate(1.MHz(), 48.kHz()).unwrap();
+        assert!(audio.plli2s.is_valid());
+
+        assert!(!(PLLI2SConfig { sai_div_factor: 0, ..audio.plli2s }).is_valid());
+        assert!(!(PLLI2SConfig { sai_div_factor: 33, ..audio.plli2s }).is_valid());
+        assert!(!(PLLI2SConfig { plli2sr: 8, ..audio.plli2s }).is_valid());
+    }
+
+    #[test]
+    fn pllsai_solver_ili9341() {
+        let pixel = PLLSAIConfig::for_pixel_clock(1.MHz(), 6.MHz()).unwrap();
+
+        assert_eq!(pixel.freq, Hertz::MHz(6));
+        assert_eq!(pixel.error, Hertz::Hz(0));
+        assert_eq!(
+            pixel.freq.raw(),
+            (pixel.pllsai.pllsain as u32) * 1_000_000 /
+                (pixel.pllsai.pllsair as u32) /
+                pixel.pllsai.lcd_div_factor.division_factor()
+        );
+    }
+
+    #[test]
+    fn pllsai_solver_inexact() {
+        let pixel = PLLSAIConfig::for_pixel_clock(2.MHz(), 9_700_001.Hz()).unwrap();
+
+        assert_eq!(pixel.error.raw(), pixel.freq.raw().abs_diff(9_700_001));
+        assert!(pixel.error < Hertz::kHz(10));
+    }
+
+    #[test]
+    fn pllsai_config_validation() {
+        let pixel = PLLSAIConfig::for_pixel_clock(1.MHz(), 6.MHz()).unwrap();
+        assert!(pixel.pllsai.is_valid(1.MHz()));
+
+        let config = PLLSAIConfig { pllsain: 192, ..pixel.pllsai };
+        assert!(config.is_valid(2.MHz()));
+        assert!(!config.is_valid(Hertz::kHz(500)));
+        assert!(!config.is_valid(3.MHz()));
+        assert!(!(PLLSAIConfig { pllsain: 49, ..config }).is_valid(2.MHz()));
+        assert!(!(PLLSAIConfig { pllsaiq: 1, ..config }).is_valid(2.MHz()));
+        assert!(!(PLLSAIConfig { pllsaiq: 16, ..config }).is_valid(2.MHz()));
+        assert!(!(PLLSAIConfig { pllsair: 8, ..config }).is_valid(2.MHz()));
+    }
+
+    #[test]
+    fn timer_clock_multiplier() {
+        let hclk: Hertz = 180.MHz();
+
+        assert_eq!(
+            TimerClockMultiplier::X2.timer_freq(hclk, hclk, APBPrescaler::NotDivided),
+            Hertz::MHz(180)
+        );
+        assert_eq!(
+            TimerClockMultiplier::X2.timer_freq(hclk, hclk / 4, APBPrescaler::DividedBy4),
+            Hertz::MHz(90)
+        );
+        assert_eq!(
+            TimerClockMultiplier::X4.timer_freq(hclk, hclk / 4, APBPrescaler::DividedBy4),
+            Hertz::MHz(180)
+        );
+        assert_eq!(
+            TimerClockMultiplier::X4.timer_freq(hclk, hclk / 8, APBPrescaler::DividedBy8),
+            Hertz::MHz(90)
+        );
+    }
+
+    #[test]
+    fn peripheral_clocks() {
+        assert_eq!(GPIOC::CLOCK, PeripheralClock::new(Bus::AHB1, 2));
+        assert_eq!(SPI5::CLOCK, PeripheralClock::new(Bus::APB2, 20));
+        assert_eq!(I2C3::CLOCK, PeripheralClock::new(Bus::APB1, 23));
+    }
+
+    #[test]
+    fn reset_reason_from_csr() {
+        // value after power-up with LSI on
+        let reason = ResetReason::from_bits(0x0e00_0003);
+        assert!(reason.power_on && reason.pin && reason.brown_out);
+        assert!(
+            !reason.software &&
+                !reason.independent_watchdog &&
+                !reason.window_watchdog &&
+                !reason.low_power
+        );
+
+        let reason = ResetReason::from_bits(1 << 29);
+        assert_eq!(reason, ResetReason { independent_watchdog: true, ..Default::default() });
+    }
+
+    #[test]
+    fn pll_solver_const() {
+        const PLL: PLLConfig = PLLConfig::hse(HSEConfig::oscillator(Hertz::MHz(8)))
+            .sysclk(Hertz::MHz(180))
+            .build_or_panic();
+
+        assert_eq!(PLL.plln, 180);
+        assert_eq!(PLL.pllq, 8);
+    }
 }
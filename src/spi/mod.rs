@@ -1,154 +1,121 @@
-use core::{ fmt, mem, ptr::addr_of, slice };
+use core::{ fmt, mem, slice };
 
-use crate::{ pac, rcc::RCC, Peripheral, PeripheralRef };
+use crate::{ pac, rcc::{ Bus, Clocks, PeripheralClock, RCC }, time::Hertz, Peripheral };
 
-pub struct SPI(pac::spi1::RegisterBlock);
-
-pub struct SPI1;
-
-impl PeripheralRef for SPI1 {
-    type Output = SPI;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::SPI1::PTR as *mut Self::Output).as_mut().unwrap() }
-    }
+/// SPI driver owning the peripheral handle `I` and its SCK, MISO and MOSI pins.
+pub struct SPI<I: Instance, PINS> {
+    spi: I,
+    pins: PINS,
 }
 
-pub struct SPI2;
+/// Owned handle of SPI1, obtained from [`Peripherals`](crate::Peripherals).
+pub struct SPI1(pub(crate) ());
 
-impl PeripheralRef for SPI2 {
-    type Output = SPI;
-
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::SPI2::PTR as *mut Self::Output).as_mut().unwrap() }
-    }
+impl Instance for SPI1 {
+    const PTR: *const pac::spi1::RegisterBlock = pac::SPI1::PTR;
 }
 
-pub struct SPI3;
+impl Peripheral for SPI1 {
+    const CLOCK: PeripheralClock = PeripheralClock::SPI1;
+}
 
-impl PeripheralRef for SPI3 {
-    type Output = SPI;
+/// Owned handle of SPI2, obtained from [`Peripherals`](crate::Peripherals).
+pub struct SPI2(pub(crate) ());
 
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::SPI3::PTR as *mut Self::Output).as_mut().unwrap() }
-    }
+impl Instance for SPI2 {
+    const PTR: *const pac::spi1::RegisterBlock = pac::SPI2::PTR;
 }
 
-pub struct SPI4;
+impl Peripheral for SPI2 {
+    const CLOCK: PeripheralClock = PeripheralClock::SPI2;
+}
 
-impl PeripheralRef for SPI4 {
-    type Output = SPI;
+/// Owned handle of SPI3, obtained from [`Peripherals`](crate::Peripherals).
+pub struct SPI3(pub(crate) ());
 
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::SPI4::PTR as *mut Self::Output).as_mut().unwrap() }
-    }
+impl Instance for SPI3 {
+    const PTR: *const pac::spi1::RegisterBlock = pac::SPI3::PTR;
 }
 
-pub struct SPI5;
+impl Peripheral for SPI3 {
+    const CLOCK: PeripheralClock = PeripheralClock::SPI3;
+}
 
-impl PeripheralRef for SPI5 {
-    type Output = SPI;
+/// Owned handle of SPI4, obtained from [`Peripherals`](crate::Peripherals).
+pub struct SPI4(pub(crate) ());
 
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::SPI5::PTR as *mut Self::Output).as_mut().unwrap() }
-    }
+impl Instance for SPI4 {
+    const PTR: *const pac::spi1::RegisterBlock = pac::SPI4::PTR;
 }
 
-pub struct SPI6;
+impl Peripheral for SPI4 {
+    const CLOCK: PeripheralClock = PeripheralClock::SPI4;
+}
 
-impl PeripheralRef for SPI6 {
-    type Output = SPI;
+/// Owned handle of SPI5, obtained from [`Peripherals`](crate::Peripherals).
+pub struct SPI5(pub(crate) ());
 
-    fn take() -> &'static mut Self::Output {
-        unsafe { (pac::SPI6::PTR as *mut Self::Output).as_mut().unwrap() }
-    }
+impl Instance for SPI5 {
+    const PTR: *const pac::spi1::RegisterBlock = pac::SPI5::PTR;
 }
 
-impl Peripheral for SPI {
-    fn enable_clock(&mut self) {
-        let rcc = RCC::take();
-        let ptr = addr_of!(self.0);
+impl Peripheral for SPI5 {
+    const CLOCK: PeripheralClock = PeripheralClock::SPI5;
+}
 
-        match ptr {
-            pac::SPI1::PTR => rcc.apb2enr().modify(|_, w| w.spi1en().set_bit()),
-            pac::SPI2::PTR => rcc.apb1enr().modify(|_, w| w.spi2en().set_bit()),
-            pac::SPI3::PTR => rcc.apb1enr().modify(|_, w| w.spi3en().set_bit()),
-            pac::SPI4::PTR => rcc.apb2enr().modify(|_, w| w.spi4en().set_bit()),
-            pac::SPI5::PTR => rcc.apb2enr().modify(|_, w| w.spi5en().set_bit()),
-            pac::SPI6::PTR => rcc.apb2enr().modify(|_, w| w.spi6en().set_bit()),
-            _ => panic!(),
-        }
-    }
+/// Owned handle of SPI6, obtained from [`Peripherals`](crate::Peripherals).
+pub struct SPI6(pub(crate) ());
 
-    fn disable_clock(&mut self) {
-        let rcc = RCC::take();
-        let ptr = addr_of!(self.0);
-
-        match ptr {
-            pac::SPI1::PTR => rcc.apb2enr().modify(|_, w| w.spi1en().clear_bit()),
-            pac::SPI2::PTR => rcc.apb1enr().modify(|_, w| w.spi2en().clear_bit()),
-            pac::SPI3::PTR => rcc.apb1enr().modify(|_, w| w.spi3en().clear_bit()),
-            pac::SPI4::PTR => rcc.apb2enr().modify(|_, w| w.spi4en().clear_bit()),
-            pac::SPI5::PTR => rcc.apb2enr().modify(|_, w| w.spi5en().clear_bit()),
-            pac::SPI6::PTR => rcc.apb2enr().modify(|_, w| w.spi6en().clear_bit()),
-            _ => panic!(),
-        }
-    }
+impl Instance for SPI6 {
+    const PTR: *const pac::spi1::RegisterBlock = pac::SPI6::PTR;
+}
 
-    fn reset(&mut self) {
-        let rcc = RCC::take();
-        let ptr = addr_of!(self.0);
+impl Peripheral for SPI6 {
+    const CLOCK: PeripheralClock = PeripheralClock::SPI6;
+}
 
-        match ptr {
-            pac::SPI1::PTR => {
-                rcc.apb2rstr().modify(|_, w| w.spi1rst().set_bit());
-                rcc.apb2rstr().modify(|_, w| w.spi1rst().clear_bit());
-            }
-            pac::SPI2::PTR => {
-                rcc.apb1rstr().modify(|_, w| w.spi2rst().set_bit());
-                rcc.apb1rstr().modify(|_, w| w.spi2rst().clear_bit());
-            }
-            pac::SPI3::PTR => {
-                rcc.apb1rstr().modify(|_, w| w.spi3rst().set_bit());
-                rcc.apb1rstr().modify(|_, w| w.spi3rst().clear_bit());
-            }
-            pac::SPI4::PTR => {
-                rcc.apb2rstr().modify(|_, w| w.spi4rst().set_bit());
-                rcc.apb2rstr().modify(|_, w| w.spi4rst().clear_bit());
-            }
-            pac::SPI5::PTR => {
-                rcc.apb2rstr().modify(|_, w| w.spi5rst().set_bit());
-                rcc.apb2rstr().modify(|_, w| w.spi5rst().clear_bit());
-            }
-            pac::SPI6::PTR => {
-                rcc.apb2rstr().modify(|_, w| w.spi6rst().set_bit());
-                rcc.apb2rstr().modify(|_, w| w.spi6rst().clear_bit());
-            }
-            _ => panic!(),
-        }
-    }
+/// SPI1 to SPI6, used to match pins with the peripheral they are wired to.
+pub trait Instance: Peripheral {
+    const PTR: *const pac::spi1::RegisterBlock;
 }
 
-impl SPI {
-    pub fn enable(&mut self) {
-        self.0.cr1().modify(|_, w| w.spe().set_bit());
-    }
+/// Pins able to carry the SCK signal of `SPI`, see [`crate::gpio::alt`].
+pub trait SckPin<SPI> {}
+
+pub trait MisoPin<SPI> {}
+
+pub trait MosiPin<SPI> {}
+
+impl<I: Instance, SCK: SckPin<I>, MISO: MisoPin<I>, MOSI: MosiPin<I>> SPI<I, (SCK, MISO, MOSI)> {
+    /// Takes ownership of `spi` and configures it with `pins` as SCK, MISO and MOSI.
+    ///
+    /// Use [`NoPin`](crate::gpio::alt::NoPin) for MISO or MOSI when the signal is unused.
+    /// Fails if `baud_rate` divides the bus clock in `clocks` to an SCK above the
+    /// datasheet limit, 22.5 MHz on APB1 and 45 MHz on APB2.
+    pub fn new(
+        mut spi: I,
+        rcc: &mut RCC,
+        clocks: &Clocks,
+        pins: (SCK, MISO, MOSI),
+        config: SPIConfig
+    ) -> Result<Self> {
+        let SPIConfig { mode, bus_config, baud_rate, data_format, cpol, cpha, ssm } = config;
 
-    pub fn disable(&mut self) {
-        self.0.cr1().modify(|_, w| w.spe().clear_bit());
-    }
+        let (f_pclk, f_max) = match I::CLOCK.bus {
+            Bus::APB1 => (clocks.pclk1(), Hertz::kHz(22_500)),
+            _ => (clocks.pclk2(), Hertz::MHz(45)),
+        };
 
-    pub fn is_enabled(&self) -> bool {
-        self.0.cr1().read().spe().bit()
-    }
+        if f_pclk / (2 << (baud_rate as u32)) > f_max {
+            return Err(Error::InitError("Baud rate is too high"));
+        }
 
-    pub fn init(&mut self, config: SPIConfig) -> Result<()> {
-        self.enable_clock();
-        self.reset();
+        spi.enable_clock(rcc);
+        spi.reset(rcc);
 
-        let SPIConfig { mode, bus_config, baud_rate, data_format, cpol, cpha, ssm } = config;
+        let mut spi = Self { spi, pins };
 
-        self.0.cr1().modify(|_, w| {
+        spi.regs().cr1().modify(|_, w| {
             w.mstr().bit(mode == Mode::Master);
             w.ssi().bit(mode == Mode::Master);
             match bus_config {
@@ -169,22 +136,47 @@ impl SPI {
             w.cpha().bit(cpha == ClockPhase::SecondClockTransition);
             w.ssm().bit(ssm)
         });
-        self.0.cr2().modify(|_, w| w.ssoe().bit(ssm));
+        spi.regs().cr2().modify(|_, w| w.ssoe().bit(ssm));
 
-        self.enable();
+        spi.enable();
 
-        Ok(())
+        Ok(spi)
+    }
+}
+
+impl<I: Instance, PINS> SPI<I, PINS> {
+    /// Disables the peripheral and gives its handle and pins back.
+    pub fn release(mut self) -> (I, PINS) {
+        self.disable();
+        (self.spi, self.pins)
+    }
+
+    #[inline]
+    fn regs(&self) -> &pac::spi1::RegisterBlock {
+        unsafe { &*I::PTR }
+    }
+
+    pub fn enable(&mut self) {
+        self.regs().cr1().modify(|_, w| w.spe().set_bit());
+    }
+
+    pub fn disable(&mut self) {
+        self.regs().cr1().modify(|_, w| w.spe().clear_bit());
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.regs().cr1().read().spe().bit()
     }
 
     pub fn write_data(&mut self, data: &[u8]) -> Result<()> {
-        let dff = DataFrameFormat::from_bits(self.0.cr1().read().dff().bit() as u32);
+        let dff = DataFrameFormat::from_bits(self.regs().cr1().read().dff().bit() as u32);
 
         unsafe {
             match dff {
                 DataFrameFormat::Format8Bit => {
                     for byte in data {
-                        self.0.dr().write(|w| w.dr().set(*byte as _));
-                        while self.0.sr().read().txe().bit_is_clear() {}
+                        self.regs().dr().write(|w| w.dr().set(*byte as _));
+                        while self.regs().sr().read().txe().bit_is_clear() {}
                     }
                 }
                 DataFrameFormat::Format16Bit => {
@@ -193,17 +185,17 @@ impl SPI {
                         data.len() / mem::size_of::<u16>()
                     );
                     for word in data {
-                        self.0.dr().write(|w| w.dr().set(*word));
-                        while self.0.sr().read().txe().bit_is_clear() {}
+                        self.regs().dr().write(|w| w.dr().set(*word));
+                        while self.regs().sr().read().txe().bit_is_clear() {}
                     }
                 }
             }
 
             // wait for busy flag is reset
-            while self.0.sr().read().bsy().bit_is_set() {}
+            while self.regs().sr().read().bsy().bit_is_set() {}
 
-            let _ = self.0.dr().read().bits();
-            _ = self.0.sr().read().bits();
+            let _ = self.regs().dr().read().bits();
+            _ = self.regs().sr().read().bits();
         }
 
         Ok(())
@@ -0,0 +1,21 @@
+//! Frequency and duration units.
+//!
+//! All public frequencies of the crate are expressed with these `fugit` types, which
+//! are plain `u32` wrappers with `const` constructors and conversions.
+//!
+//! ```ignore
+//! use stm32_hal::time::{ Hertz, RateExtU32 };
+//!
+//! const SYSCLK: Hertz = Hertz::MHz(180);
+//! let scl: Hertz = 400.kHz();
+//! ```
+
+pub use fugit::{
+    ExtU32,
+    HertzU32 as Hertz,
+    KilohertzU32 as KiloHertz,
+    MegahertzU32 as MegaHertz,
+    MicrosDurationU32 as MicroSeconds,
+    MillisDurationU32 as MilliSeconds,
+    RateExtU32,
+};